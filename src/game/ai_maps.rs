@@ -0,0 +1,43 @@
+use crate::rltk;
+use rltk::Point;
+use super::{State, DijkstraMap};
+
+// Which of the shared maps a mob is walking downhill on
+#[derive(Clone, Copy)]
+pub enum Goal {
+    Player,
+    AwayFromPlayer,
+    Loot
+}
+
+// Dijkstra maps shared by every mob. They're built once per turn, before the mobs
+// act, so a mob only has to look at its neighbours to know which way to go.
+pub struct AiMaps {
+    pub to_player : DijkstraMap,
+    pub from_player : DijkstraMap,
+    pub to_items : DijkstraMap
+}
+
+impl AiMaps {
+    pub fn get(&self, goal : Goal) -> &DijkstraMap {
+        match goal {
+            Goal::Player => &self.to_player,
+            Goal::AwayFromPlayer => &self.from_player,
+            Goal::Loot => &self.to_items
+        }
+    }
+}
+
+// Only the terrain blocks these maps; mobs standing in each other's way is sorted
+// out when one picks its step.
+pub fn rebuild(gs : &mut State) {
+    gs.map.refresh_blocked();
+    let to_player = DijkstraMap::new(&gs.map, &[gs.player_position()]);
+    let from_player = to_player.fleeing(&gs.map);
+    let items : Vec<Point> = gs.world.items.iter()
+        .filter(|(_id, item)| item.is_loot())
+        .filter_map(|(id, _item)| gs.world.positions.get(id).cloned())
+        .collect();
+    let to_items = DijkstraMap::new(&gs.map, &items);
+    gs.ai_maps = Some(AiMaps{ to_player, from_player, to_items });
+}
@@ -0,0 +1,117 @@
+use super::{ Map, Rect, TileType, GameRng, map_builder, map_builder::{MapBuilder, SpawnRegion} };
+use crate::rltk;
+use rltk::Point;
+use rand::Rng;
+use std::cmp::max;
+
+const ROOM_ATTEMPTS : i32 = 240;
+const ROOM_MAX_SIZE : i32 = 10;
+
+// Binary space partition: the map is cut into ever smaller rectangles and rooms are
+// placed inside them, so they spread over the whole level and never touch. Rooms are
+// joined left to right.
+pub struct BspMapBuilder {
+    map : Map,
+    rooms : Vec<Rect>,
+    rects : Vec<Rect>
+}
+
+impl BspMapBuilder {
+    pub fn new(width : i32, height : i32) -> BspMapBuilder {
+        BspMapBuilder{ map: Map::new(width, height), rooms: Vec::new(), rects: Vec::new() }
+    }
+
+    // Splits a rectangle into quarters, which become candidates for rooms
+    fn add_subrects(&mut self, rect : Rect) {
+        let half_width = max((rect.x2 - rect.x1) / 2, 1);
+        let half_height = max((rect.y2 - rect.y1) / 2, 1);
+
+        self.rects.push(Rect::new(rect.x1, rect.y1, rect.x1 + half_width, rect.y1 + half_height));
+        self.rects.push(Rect::new(rect.x1, rect.y1 + half_height, rect.x1 + half_width, rect.y2));
+        self.rects.push(Rect::new(rect.x1 + half_width, rect.y1, rect.x2, rect.y1 + half_height));
+        self.rects.push(Rect::new(rect.x1 + half_width, rect.y1 + half_height, rect.x2, rect.y2));
+    }
+
+    // A room somewhere near the top left of the rectangle; it may spill out of it
+    fn random_sub_rect(rect : Rect, rng : &mut GameRng) -> Rect {
+        let rect_width = (rect.x2 - rect.x1).clamp(1, ROOM_MAX_SIZE);
+        let rect_height = (rect.y2 - rect.y1).clamp(1, ROOM_MAX_SIZE);
+        let w = max(3, rng.gen_range(1, rect_width + 1) - 1) + 1;
+        let h = max(3, rng.gen_range(1, rect_height + 1) - 1) + 1;
+        let x = rect.x1 + rng.gen_range(0, 6);
+        let y = rect.y1 + rng.gen_range(0, 6);
+        Rect::new(x, y, x + w, y + h)
+    }
+
+    // A room fits if it, and a two tile border around it, is inside the map and solid rock
+    fn is_possible(&self, rect : Rect) -> bool {
+        for y in rect.y1 - 2 ..= rect.y2 + 2 {
+            for x in rect.x1 - 2 ..= rect.x2 + 2 {
+                if x < 1 || y < 1 || x > self.map.width - 2 || y > self.map.height - 2 { return false; }
+                if self.map.tiles[((y * self.map.width) + x) as usize] != TileType::Wall { return false; }
+            }
+        }
+        true
+    }
+}
+
+impl MapBuilder for BspMapBuilder {
+    fn name(&self) -> &'static str { "bsp" }
+
+    fn build_map(&mut self, rng : &mut GameRng) {
+        self.rects.clear();
+        let first_room = Rect::new(2, 2, self.map.width - 3, self.map.height - 3);
+        self.rects.push(first_room);
+        self.add_subrects(first_room);
+
+        for _i in 0..ROOM_ATTEMPTS {
+            let rect = self.rects[rng.gen_range(0, self.rects.len())];
+            let candidate = BspMapBuilder::random_sub_rect(rect, rng);
+
+            if self.is_possible(candidate) {
+                map_builder::apply_room(&mut self.map, &candidate);
+                self.rooms.push(candidate);
+                self.add_subrects(rect);
+            }
+        }
+
+        // Corridors join each room to the next one along, from a random spot in each
+        self.rooms.sort_by_key(|r| r.x1);
+        for i in 1..self.rooms.len() {
+            let from = self.rooms[i-1];
+            let to = self.rooms[i];
+            let start_x = rng.gen_range(from.x1, from.x2);
+            let start_y = rng.gen_range(from.y1, from.y2);
+            let end_x = rng.gen_range(to.x1, to.x2);
+            let end_y = rng.gen_range(to.y1, to.y2);
+            map_builder::apply_horizontal_tunnel(&mut self.map, start_x, end_x, start_y);
+            map_builder::apply_vertical_tunnel(&mut self.map, start_y, end_y, end_x);
+        }
+
+        map_builder::place_doors(&mut self.map, &self.rooms, rng);
+        let stairs = self.stairs_position();
+        self.map.tiles[((stairs.y * self.map.width) + stairs.x) as usize] = TileType::Stairs;
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn starting_position(&self) -> Point {
+        let (x, y) = self.rooms[0].center();
+        Point::new(x, y)
+    }
+
+    fn stairs_position(&self) -> Point {
+        let (x, y) = self.rooms[self.rooms.len()-1].center();
+        Point::new(x, y)
+    }
+
+    fn rooms(&self) -> Vec<Rect> {
+        self.rooms.clone()
+    }
+
+    fn spawn_regions(&self) -> Vec<SpawnRegion> {
+        self.rooms.iter().skip(1).map(SpawnRegion::from_rect).collect()
+    }
+}
@@ -0,0 +1,37 @@
+use crate::rltk;
+use rltk::Point;
+use super::State;
+use std::cmp::{max, min};
+
+// The console, and the part of it the map is drawn in (the rest is the log panel)
+pub const SCREEN_WIDTH : i32 = 80;
+pub const SCREEN_HEIGHT : i32 = 50;
+pub const VIEW_WIDTH : i32 = 80;
+pub const VIEW_HEIGHT : i32 = 43;
+
+// Which part of the map is on screen. It follows the player, but stops at the
+// edges of the map rather than showing the void past them.
+pub struct Camera {
+    pub min_x : i32,
+    pub min_y : i32
+}
+
+impl Camera {
+    pub fn new(gs : &State) -> Camera {
+        let player_pos = gs.player_position();
+        let min_x = min(max(0, player_pos.x - (VIEW_WIDTH / 2)), max(0, gs.map.width - VIEW_WIDTH));
+        let min_y = min(max(0, player_pos.y - (VIEW_HEIGHT / 2)), max(0, gs.map.height - VIEW_HEIGHT));
+        Camera{ min_x, min_y }
+    }
+
+    // Where a map position is drawn, if it is on screen at all
+    pub fn world_to_screen(&self, pos : Point) -> Option<Point> {
+        let screen = Point::new(pos.x - self.min_x, pos.y - self.min_y);
+        if (0..VIEW_WIDTH).contains(&screen.x) && (0..VIEW_HEIGHT).contains(&screen.y) { Some(screen) } else { None }
+    }
+
+    // The map position under a screen position (e.g. the mouse), if it is over the map view
+    pub fn screen_to_world(&self, x : i32, y : i32) -> Option<Point> {
+        if (0..VIEW_WIDTH).contains(&x) && (0..VIEW_HEIGHT).contains(&y) { Some(Point::new(x + self.min_x, y + self.min_y)) } else { None }
+    }
+}
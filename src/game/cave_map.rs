@@ -0,0 +1,133 @@
+use super::{ Map, TileType, GameRng, DijkstraMap, dijkstra::UNREACHABLE, map_builder::{MapBuilder, SpawnRegion} };
+use crate::rltk;
+use rltk::{Point, Algorithm2D};
+use rand::Rng;
+use std::collections::BTreeMap;
+
+const FLOOR_PERCENT : i32 = 45;
+const SMOOTHING_PASSES : i32 = 15;
+// Spawn regions are the floor within squares of this size
+const REGION_SIZE : i32 = 16;
+const MIN_REGION_TILES : usize = 20;
+// Nothing spawns this close (in steps) to where the player starts
+const SAFE_DISTANCE : f32 = 10.0;
+
+// Natural-looking caves: random noise, smoothed by a cellular automaton until
+// it clumps into caverns. Pockets that can't be reached from the start are filled
+// in, and the stairs go as far from the start as possible.
+pub struct CaveMapBuilder {
+    map : Map,
+    start : Point,
+    stairs : Point,
+    regions : Vec<SpawnRegion>
+}
+
+impl CaveMapBuilder {
+    pub fn new(width : i32, height : i32) -> CaveMapBuilder {
+        CaveMapBuilder{ map: Map::new(width, height), start: Point::new(width / 2, height / 2), stairs: Point::new(width / 2, height / 2), regions: Vec::new() }
+    }
+
+    fn idx(&self, x : i32, y : i32) -> usize {
+        ((y * self.map.width) + x) as usize
+    }
+
+    // A tile with more than four walls around it becomes wall, as does one with none
+    // (which breaks up big open areas); everything else becomes floor.
+    fn smooth(&mut self) {
+        let mut new_tiles = self.map.tiles.clone();
+        for y in 1 .. self.map.height - 1 {
+            for x in 1 .. self.map.width - 1 {
+                let mut walls = 0;
+                for dy in -1 ..= 1 {
+                    for dx in -1 ..= 1 {
+                        if (dx != 0 || dy != 0) && self.map.tiles[self.idx(x + dx, y + dy)] == TileType::Wall { walls += 1; }
+                    }
+                }
+                new_tiles[self.idx(x, y)] = if walls > 4 || walls == 0 { TileType::Wall } else { TileType::Floor };
+            }
+        }
+        self.map.tiles = new_tiles;
+    }
+
+    // The floor tile closest to the middle of the map
+    fn find_start(&self) -> Option<Point> {
+        let centre = Point::new(self.map.width / 2, self.map.height / 2);
+        let mut best : Option<(Point, i32)> = None;
+        for (idx, tile) in self.map.tiles.iter().enumerate() {
+            if *tile != TileType::Floor { continue; }
+            let pos = self.map.index_to_point2d(idx as i32);
+            let distance = (pos.x - centre.x).abs() + (pos.y - centre.y).abs();
+            match best {
+                Some((_, best_distance)) if best_distance <= distance => {}
+                _ => { best = Some((pos, distance)); }
+            }
+        }
+        best.map(|(pos, _)| pos)
+    }
+}
+
+impl MapBuilder for CaveMapBuilder {
+    fn name(&self) -> &'static str { "caves" }
+
+    fn build_map(&mut self, rng : &mut GameRng) {
+        for y in 1 .. self.map.height - 1 {
+            for x in 1 .. self.map.width - 1 {
+                let idx = self.idx(x, y);
+                self.map.tiles[idx] = if rng.gen_range(0, 100) < FLOOR_PERCENT { TileType::Floor } else { TileType::Wall };
+            }
+        }
+        for _i in 0 .. SMOOTHING_PASSES {
+            self.smooth();
+        }
+
+        self.start = match self.find_start() {
+            Some(start) => start,
+            None => {
+                // Nothing but rock; give the player somewhere to stand
+                let idx = self.idx(self.start.x, self.start.y);
+                self.map.tiles[idx] = TileType::Floor;
+                self.start
+            }
+        };
+
+        // Flood fill out from the start: anything it doesn't reach is walled up
+        self.map.refresh_blocked();
+        let distances = DijkstraMap::new(&self.map, &[self.start]);
+        for (idx, tile) in self.map.tiles.iter_mut().enumerate() {
+            if *tile == TileType::Floor && distances.distances[idx] == UNREACHABLE { *tile = TileType::Wall; }
+        }
+
+        self.stairs = distances.furthest().unwrap_or(self.start);
+        let stairs_idx = self.idx(self.stairs.x, self.stairs.y);
+        self.map.tiles[stairs_idx] = TileType::Stairs;
+
+        // Group the reachable floor into squares, leaving out what's next to the start
+        let mut regions : BTreeMap<(i32, i32), Vec<Point>> = BTreeMap::new();
+        for (idx, tile) in self.map.tiles.iter().enumerate() {
+            let distance = distances.distances[idx];
+            if *tile != TileType::Floor || distance == UNREACHABLE || distance < SAFE_DISTANCE { continue; }
+            let pos = self.map.index_to_point2d(idx as i32);
+            regions.entry((pos.x / REGION_SIZE, pos.y / REGION_SIZE)).or_default().push(pos);
+        }
+        self.regions = regions.into_values()
+            .map(|tiles| SpawnRegion{ tiles })
+            .filter(|region| region.tiles.len() >= MIN_REGION_TILES)
+            .collect();
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn starting_position(&self) -> Point {
+        self.start
+    }
+
+    fn stairs_position(&self) -> Point {
+        self.stairs
+    }
+
+    fn spawn_regions(&self) -> Vec<SpawnRegion> {
+        self.regions.clone()
+    }
+}
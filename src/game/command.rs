@@ -0,0 +1,39 @@
+use crate::rltk;
+use rltk::Point;
+extern crate serde;
+use serde::{Serialize, Deserialize};
+
+// Everything the player can ask the game to do. The front end turns key presses
+// into these; headless callers (tests, bots) issue them directly.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Command {
+    Move(i32, i32),
+    Wait,
+    PickUp,
+    Descend,
+    Ascend,
+    CloseDoor,
+    Search,
+    ToggleSneak,
+
+    ShowUseMenu,
+    ShowDropMenu,
+    ShowEquipMenu,
+    ShowUnequipMenu,
+    ShowCharacterInfo,
+    ShowHelp,
+    Cancel,
+
+    // Indices refer to the player's backpack (inventory.items), except for
+    // UnequipItem which refers to the equipped list.
+    UseItem(i32),
+    DropItem(i32),
+    EquipItem(i32),
+    UnequipItem(i32),
+    Target(Point),
+
+    LevelUp(LevelUpChoice)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum LevelUpChoice { HitPoints, Power }
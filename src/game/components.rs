@@ -0,0 +1,153 @@
+use crate::rltk;
+use rltk::{RGB, Point};
+extern crate serde;
+use serde::{Serialize, Deserialize};
+
+// Components that are plain data. Fighter, Inventory, Item and Equippable live
+// alongside the code that works on them.
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Renderable {
+    pub glyph : u8,
+    pub fg : RGB,
+    pub render_order : i32 // Lower numbers are drawn on top
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Viewshed {
+    pub visible_tiles : Vec<Point>,
+    pub range : i32
+}
+
+impl Viewshed {
+    pub fn new(range : i32) -> Viewshed {
+        Viewshed{ visible_tiles: Vec::new(), range }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Name {
+    pub name : String
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Confusion {
+    pub turns : i32
+}
+
+// Gives off light around wherever it is, or around whoever is carrying it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LightSource {
+    pub colour : RGB,
+    pub range : i32
+}
+
+// Lets a mob attack from a distance, by shooting or casting something at its target
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RangedAttack {
+    pub range : i32,
+    pub verb : String, // e.g. "spits acid at"
+    pub glyph : u8,
+    pub colour : RGB
+}
+
+// Marks an entity as a monster, driven by the mob AI system
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Mob {
+    // Animals can't work a door handle; closed doors are walls to them
+    #[serde(default = "opens_doors")]
+    pub opens_doors : bool,
+    // Where something caught its attention (e.g. an alarm), to go and look at
+    #[serde(default)]
+    pub alerted_to : Option<Point>,
+    #[serde(default)]
+    pub ai : MobAi,
+    // Where the mob last saw the player, if it hasn't given up on them yet
+    #[serde(default)]
+    pub memory : Option<PlayerMemory>,
+    #[serde(default)]
+    pub state : AiState,
+    // Hidden traps the mob has seen go off, which it keeps clear of from then on
+    #[serde(default)]
+    pub known_traps : Vec<Point>
+}
+
+// What a mob is up to, which decides what it does with its turn
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum AiState {
+    Asleep,
+    // Roaming the level, along a path to somewhere else once it has picked one
+    Wandering { path : Vec<Point> },
+    Hunting,
+    Fleeing
+}
+
+impl Default for AiState {
+    fn default() -> AiState { AiState::Wandering{ path: Vec::new() } }
+}
+
+impl AiState {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            AiState::Asleep => "asleep",
+            AiState::Wandering{..} => "wandering",
+            AiState::Hunting => "hunting",
+            AiState::Fleeing => "fleeing"
+        }
+    }
+}
+
+pub fn opens_doors() -> bool { true }
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PlayerMemory {
+    pub last_seen : Point,
+    // The mob's own turns since then, and how many of them it has spent looking around
+    pub turns_ago : i32,
+    pub turns_searched : i32
+}
+
+impl PlayerMemory {
+    pub fn new(last_seen : Point) -> PlayerMemory {
+        PlayerMemory{ last_seen, turns_ago: 0, turns_searched: 0 }
+    }
+}
+
+// How a mob behaves beyond walking up to the player and hitting them
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct MobAi {
+    // Runs from the player once its HP drops below this percentage of its maximum
+    pub flee_below : i32,
+    // Tries to stay this many tiles from the player, rather than closing in
+    pub keep_distance : i32,
+    // Goes after items lying about when it hasn't seen the player, and carries them off
+    pub seeks_loot : bool
+}
+
+// The player's progression. Only the player entity has one.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Player {
+    pub dungeon_level : i32,
+    pub xp : i32,
+    pub level : i32,
+    // Moving carefully: slower, but much quieter
+    #[serde(default)]
+    pub sneaking : bool
+}
+
+impl Player {
+    pub fn xp_to_level(&self) -> i32 {
+        200 + (self.level * 150)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BlocksTile {}
+
+// Entities gain `speed` energy every tick and act once they have enough; see scheduler.rs
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Energy {
+    pub speed : i32,
+    pub energy : i32
+}
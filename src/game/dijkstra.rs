@@ -0,0 +1,67 @@
+use crate::rltk;
+use rltk::{Point, Algorithm2D, BaseMap};
+use super::Map;
+use std::collections::VecDeque;
+
+pub const UNREACHABLE : f32 = f32::MAX;
+// Scales a map's distances for fleeing. Past -1, running further away beats
+// running into a dead end that happens to be next to you.
+const FLEE_FACTOR : f32 = -1.2;
+
+// How far every tile is from the nearest start tile, walking the map's exits.
+// Tiles that can't be reached at all are UNREACHABLE.
+pub struct DijkstraMap {
+    width : i32,
+    pub distances : Vec<f32>
+}
+
+impl DijkstraMap {
+    pub fn new(map : &Map, starts : &[Point]) -> DijkstraMap {
+        let mut distances = vec![UNREACHABLE; (map.width * map.height) as usize];
+        for start in starts.iter() {
+            distances[map.point2d_to_index(*start) as usize] = 0.0;
+        }
+        DijkstraMap::relax(map, distances)
+    }
+
+    // The way away from this map's starts: walking downhill on it leads somewhere
+    // far from them, rather than into the nearest corner
+    pub fn fleeing(&self, map : &Map) -> DijkstraMap {
+        let distances = self.distances.iter().map(|d| if *d == UNREACHABLE { UNREACHABLE } else { d * FLEE_FACTOR }).collect();
+        DijkstraMap::relax(map, distances)
+    }
+
+    fn relax(map : &Map, mut distances : Vec<f32>) -> DijkstraMap {
+        let mut open : VecDeque<i32> = (0 .. distances.len() as i32).filter(|idx| distances[*idx as usize] != UNREACHABLE).collect();
+
+        // Keep relaxing until nothing gets any closer
+        while let Some(idx) = open.pop_front() {
+            let distance = distances[idx as usize];
+            for (exit, cost) in map.get_available_exits(idx) {
+                if distance + cost < distances[exit as usize] {
+                    distances[exit as usize] = distance + cost;
+                    open.push_back(exit);
+                }
+            }
+        }
+
+        DijkstraMap{ width: map.width, distances }
+    }
+
+    pub fn distance(&self, pos : Point) -> f32 {
+        self.distances[((pos.y * self.width) + pos.x) as usize]
+    }
+
+    // The reachable tile furthest from the starts
+    pub fn furthest(&self) -> Option<Point> {
+        let mut best : Option<(usize, f32)> = None;
+        for (idx, distance) in self.distances.iter().enumerate() {
+            if *distance == UNREACHABLE { continue; }
+            match best {
+                Some((_, best_distance)) if best_distance >= *distance => {}
+                _ => { best = Some((idx, *distance)); }
+            }
+        }
+        best.map(|(idx, _)| Point::new(idx as i32 % self.width, idx as i32 / self.width))
+    }
+}
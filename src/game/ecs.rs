@@ -0,0 +1,280 @@
+use crate::rltk;
+use rltk::Point;
+use super::{Renderable, Viewshed, Name, Fighter, Inventory, Confusion, Item, Equippable, Mob, Player, BlocksTile, Energy, Trap, LightSource, RangedAttack};
+extern crate serde;
+use serde::{Serialize, Deserialize};
+
+// A handle to an entity. The generation changes whenever a slot is reused, so an
+// ID kept after its entity was removed simply stops resolving instead of pointing
+// at whatever moved into the slot.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EntityId {
+    index : usize,
+    generation : u32
+}
+
+// One component type, stored by entity index. Each entry remembers the generation
+// it was added for, so a stale EntityId never finds a newer entity's component.
+#[derive(Serialize, Deserialize)]
+pub struct Storage<T> {
+    data : Vec<Option<(u32, T)>>
+}
+
+impl<T> Storage<T> {
+    pub fn new() -> Storage<T> {
+        Storage{ data: Vec::new() }
+    }
+
+    pub fn insert(&mut self, id : EntityId, component : T) {
+        while self.data.len() <= id.index { self.data.push(None); }
+        self.data[id.index] = Some((id.generation, component));
+    }
+
+    pub fn remove(&mut self, id : EntityId) -> Option<T> {
+        if !self.contains(id) { return None; }
+        self.data[id.index].take().map(|(_generation, component)| component)
+    }
+
+    pub fn contains(&self, id : EntityId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id : EntityId) -> Option<&T> {
+        match self.data.get(id.index) {
+            Some(Some((generation, component))) if *generation == id.generation => { Some(component) }
+            _ => { None }
+        }
+    }
+
+    pub fn get_mut(&mut self, id : EntityId) -> Option<&mut T> {
+        match self.data.get_mut(id.index) {
+            Some(Some((generation, component))) if *generation == id.generation => { Some(component) }
+            _ => { None }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.data.iter().enumerate().filter_map(|(index, entry)| {
+            entry.as_ref().map(|(generation, component)| (EntityId{ index, generation: *generation }, component))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.data.iter_mut().enumerate().filter_map(|(index, entry)| {
+            entry.as_mut().map(|(generation, component)| (EntityId{ index, generation: *generation }, component))
+        })
+    }
+
+    pub fn ids(&self) -> Vec<EntityId> {
+        self.iter().map(|(id, _component)| id).collect()
+    }
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Storage<T> { Storage::new() }
+}
+
+// Everything one entity is made of, lifted out of the World so it can be kept
+// somewhere else (such as a level the player has left) and put back later.
+// Carried items travel with their owner.
+#[derive(Serialize, Deserialize)]
+pub struct EntityBundle {
+    position : Option<Point>,
+    renderable : Option<Renderable>,
+    viewshed : Option<Viewshed>,
+    name : Option<Name>,
+    fighter : Option<Fighter>,
+    inventory : Option<Inventory>,
+    confusion : Option<Confusion>,
+    item : Option<Item>,
+    equippable : Option<Equippable>,
+    mob : Option<Mob>,
+    player : Option<Player>,
+    blocks_tile : Option<BlocksTile>,
+    energy : Option<Energy>,
+    trap : Option<Trap>,
+    light : Option<LightSource>,
+    ranged : Option<RangedAttack>,
+    carried_items : Vec<EntityBundle>,
+    carried_equipped : Vec<EntityBundle>
+}
+
+// The entity-component store. An entity is just an ID; what it *is* comes from
+// which storages hold a component for it, and the systems act on those.
+#[derive(Serialize, Deserialize)]
+pub struct World {
+    generations : Vec<u32>,
+    alive : Vec<bool>,
+    free : Vec<usize>,
+
+    pub positions : Storage<Point>,
+    pub renderables : Storage<Renderable>,
+    pub viewsheds : Storage<Viewshed>,
+    pub names : Storage<Name>,
+    pub fighters : Storage<Fighter>,
+    pub inventories : Storage<Inventory>,
+    pub confusion : Storage<Confusion>,
+    pub items : Storage<Item>,
+    pub equippables : Storage<Equippable>,
+    pub mobs : Storage<Mob>,
+    pub players : Storage<Player>,
+    pub blocks_tile : Storage<BlocksTile>,
+    pub energy : Storage<Energy>,
+    #[serde(default)]
+    pub traps : Storage<Trap>,
+    #[serde(default)]
+    pub lights : Storage<LightSource>,
+    #[serde(default)]
+    pub ranged : Storage<RangedAttack>
+}
+
+impl World {
+    pub fn new() -> World {
+        World{
+            generations : Vec::new(),
+            alive : Vec::new(),
+            free : Vec::new(),
+            positions : Storage::new(),
+            renderables : Storage::new(),
+            viewsheds : Storage::new(),
+            names : Storage::new(),
+            fighters : Storage::new(),
+            inventories : Storage::new(),
+            confusion : Storage::new(),
+            items : Storage::new(),
+            equippables : Storage::new(),
+            mobs : Storage::new(),
+            players : Storage::new(),
+            blocks_tile : Storage::new(),
+            energy : Storage::new(),
+            traps : Storage::new(),
+            lights : Storage::new(),
+            ranged : Storage::new()
+        }
+    }
+
+    pub fn create_entity(&mut self) -> EntityId {
+        match self.free.pop() {
+            Some(index) => {
+                self.alive[index] = true;
+                EntityId{ index, generation: self.generations[index] }
+            }
+            None => {
+                self.generations.push(0);
+                self.alive.push(true);
+                EntityId{ index: self.generations.len() - 1, generation: 0 }
+            }
+        }
+    }
+
+    pub fn is_alive(&self, id : EntityId) -> bool {
+        id.index < self.alive.len() && self.alive[id.index] && self.generations[id.index] == id.generation
+    }
+
+    // Removes the entity and all of its components; its ID will never resolve again.
+    pub fn delete_entity(&mut self, id : EntityId) {
+        if !self.is_alive(id) { return; }
+
+        // Anything the entity was carrying goes with it
+        if let Some(inventory) = self.inventories.get(id) {
+            let carried : Vec<EntityId> = inventory.items.iter().chain(inventory.equipped.iter()).cloned().collect();
+            for item in carried { self.delete_entity(item); }
+        }
+
+        self.positions.remove(id);
+        self.renderables.remove(id);
+        self.viewsheds.remove(id);
+        self.names.remove(id);
+        self.fighters.remove(id);
+        self.inventories.remove(id);
+        self.confusion.remove(id);
+        self.items.remove(id);
+        self.equippables.remove(id);
+        self.mobs.remove(id);
+        self.players.remove(id);
+        self.blocks_tile.remove(id);
+        self.energy.remove(id);
+        self.traps.remove(id);
+        self.lights.remove(id);
+        self.ranged.remove(id);
+
+        self.alive[id.index] = false;
+        self.generations[id.index] += 1;
+        self.free.push(id.index);
+    }
+
+    // Removes the entity (and anything it carries), returning its components
+    pub fn extract_entity(&mut self, id : EntityId) -> EntityBundle {
+        let mut carried_items = Vec::new();
+        let mut carried_equipped = Vec::new();
+        if let Some(inventory) = self.inventories.get(id) {
+            let (items, equipped) = (inventory.items.clone(), inventory.equipped.clone());
+            for item in items { carried_items.push(self.extract_entity(item)); }
+            for item in equipped { carried_equipped.push(self.extract_entity(item)); }
+        }
+
+        let bundle = EntityBundle{
+            position : self.positions.remove(id),
+            renderable : self.renderables.remove(id),
+            viewshed : self.viewsheds.remove(id),
+            name : self.names.remove(id),
+            fighter : self.fighters.remove(id),
+            inventory : self.inventories.remove(id),
+            confusion : self.confusion.remove(id),
+            item : self.items.remove(id),
+            equippable : self.equippables.remove(id),
+            mob : self.mobs.remove(id),
+            player : self.players.remove(id),
+            blocks_tile : self.blocks_tile.remove(id),
+            energy : self.energy.remove(id),
+            trap : self.traps.remove(id),
+            light : self.lights.remove(id),
+            ranged : self.ranged.remove(id),
+            carried_items,
+            carried_equipped
+        };
+        self.delete_entity(id);
+        bundle
+    }
+
+    // Creates a new entity from an extracted one. It gets a new EntityId.
+    pub fn insert_bundle(&mut self, bundle : EntityBundle) -> EntityId {
+        let id = self.create_entity();
+        if let Some(c) = bundle.position { self.positions.insert(id, c); }
+        if let Some(c) = bundle.renderable { self.renderables.insert(id, c); }
+        if let Some(c) = bundle.viewshed { self.viewsheds.insert(id, c); }
+        if let Some(c) = bundle.name { self.names.insert(id, c); }
+        if let Some(c) = bundle.fighter { self.fighters.insert(id, c); }
+        if let Some(c) = bundle.confusion { self.confusion.insert(id, c); }
+        if let Some(c) = bundle.item { self.items.insert(id, c); }
+        if let Some(c) = bundle.equippable { self.equippables.insert(id, c); }
+        if let Some(c) = bundle.mob { self.mobs.insert(id, c); }
+        if let Some(c) = bundle.player { self.players.insert(id, c); }
+        if let Some(c) = bundle.blocks_tile { self.blocks_tile.insert(id, c); }
+        if let Some(c) = bundle.energy { self.energy.insert(id, c); }
+        if let Some(c) = bundle.trap { self.traps.insert(id, c); }
+        if let Some(c) = bundle.light { self.lights.insert(id, c); }
+        if let Some(c) = bundle.ranged { self.ranged.insert(id, c); }
+        if let Some(mut inventory) = bundle.inventory {
+            inventory.items = bundle.carried_items.into_iter().map(|b| self.insert_bundle(b)).collect();
+            inventory.equipped = bundle.carried_equipped.into_iter().map(|b| self.insert_bundle(b)).collect();
+            self.inventories.insert(id, inventory);
+        }
+        id
+    }
+
+    pub fn entities(&self) -> Vec<EntityId> {
+        (0..self.alive.len()).filter(|i| self.alive[*i]).map(|index| EntityId{ index, generation: self.generations[index] }).collect()
+    }
+
+    pub fn name(&self, id : EntityId) -> String {
+        match self.names.get(id) {
+            None => { "Something".to_string() }
+            Some(name) => { name.name.clone() }
+        }
+    }
+}
+
+impl Default for World {
+    fn default() -> World { World::new() }
+}
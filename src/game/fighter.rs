@@ -1,5 +1,5 @@
 use std::cmp::{min};
-use super::{Player, Mob};
+use super::{World, EntityId};
 extern crate serde;
 use serde::{Serialize, Deserialize};
 
@@ -23,90 +23,70 @@ impl Fighter {
             dead: false,
             xp_value : xp
         }
-    }    
-}
-
-pub trait Combat {
-    fn get_power(&self)->i32;
-    fn get_defense(&self)->i32;
-    fn take_damage(&mut self, amount:i32);
-    fn heal_damage(&mut self, amount:i32);
-    fn get_name(&self)->String;
-    fn get_hp(&self)->i32;
-    fn kill(&mut self);
-    fn xp_value(&self)->i32 { 0 }
-}
-
-pub fn attack(instigator_name: String, instigator_power : i32, target: &mut Combat) -> (i32, Vec<String>) {
-    let mut results = Vec::new();
-    let mut xp = 0;
-
-    let damage = instigator_power - target.get_defense();
-    if damage > 0 {
-        target.take_damage(damage);
-        results.push(format!("{} attacks {}, for {} hit points of damage.", instigator_name, target.get_name(), damage));
-        results.push(format!("{} has {} remaining hit points.", target.get_name(), target.get_hp()));
-        if target.get_hp() < 1 {
-            results.push(format!("{} is dead.", target.get_name()));
-            target.kill();
-            xp += target.xp_value();
-        }
-    } else {
-        results.push(format!("{} attacks {}, but lacks the power to do anything useful.", instigator_name, target.get_name()));
     }
 
-    (xp, results)
-}
-
-impl Combat for Player {
-    fn take_damage(&mut self, amount:i32) {
-        self.fighter.hp -= amount;
+    pub fn take_damage(&mut self, amount:i32) {
+        self.hp -= amount;
     }
 
-    fn heal_damage(&mut self, amount:i32) {
-        self.fighter.hp = min(self.fighter.max_hp, self.fighter.hp + amount);
+    pub fn heal_damage(&mut self, amount:i32) {
+        self.hp = min(self.max_hp, self.hp + amount);
     }
 
-    fn get_name(&self) -> String {
-        "Player".to_string()
+    pub fn kill(&mut self) {
+        self.dead = true;
     }
+}
 
-    fn get_defense(&self) -> i32 { 
-        let mut defense = self.fighter.defense;
-        for item in self.inventory.equipped.iter() {
-            defense += item.equippable.unwrap().defense_bonus;
+// Power including anything the entity has equipped
+pub fn get_power(world : &World, id : EntityId) -> i32 {
+    let mut power = match world.fighters.get(id) { None => 0, Some(f) => f.power };
+    if let Some(inventory) = world.inventories.get(id) {
+        for item in inventory.equipped.iter() {
+            if let Some(equippable) = world.equippables.get(*item) { power += equippable.power_bonus; }
         }
-        defense
     }
+    power
+}
 
-    fn get_power(&self) -> i32 { 
-        let mut power = self.fighter.power;
-        for item in self.inventory.equipped.iter() {
-            power += item.equippable.unwrap().power_bonus;
+// Defense including anything the entity has equipped
+pub fn get_defense(world : &World, id : EntityId) -> i32 {
+    let mut defense = match world.fighters.get(id) { None => 0, Some(f) => f.defense };
+    if let Some(inventory) = world.inventories.get(id) {
+        for item in inventory.equipped.iter() {
+            if let Some(equippable) = world.equippables.get(*item) { defense += equippable.defense_bonus; }
         }
-        power
     }
-
-    fn get_hp(&self) -> i32 { self.fighter.hp }
-    fn kill(&mut self) { self.fighter.dead = true; }
+    defense
 }
 
-impl Combat for Mob {
-    fn take_damage(&mut self, amount:i32) {
-        self.fighter.hp -= amount;
-    }
+// Returns the XP earned (if the target died) and the log messages
+pub fn attack(world : &mut World, instigator : EntityId, target : EntityId) -> (i32, Vec<String>) {
+    attack_with(world, instigator, target, "attacks")
+}
 
-    fn heal_damage(&mut self, amount:i32) {
-        self.fighter.hp = min(self.fighter.max_hp, self.fighter.hp + amount);
-    }
+// An attack described by its verb, e.g. "spits acid at" for one made from a distance
+pub fn attack_with(world : &mut World, instigator : EntityId, target : EntityId, verb : &str) -> (i32, Vec<String>) {
+    let mut results = Vec::new();
+    let mut xp = 0;
 
-    fn get_name(&self) -> String {
-        self.name.clone()
+    let instigator_name = world.name(instigator);
+    let target_name = world.name(target);
+    let damage = get_power(world, instigator) - get_defense(world, target);
+    if let Some(fighter) = world.fighters.get_mut(target) {
+        if damage > 0 {
+            fighter.take_damage(damage);
+            results.push(format!("{} {} {}, for {} hit points of damage.", instigator_name, verb, target_name, damage));
+            results.push(format!("{} has {} remaining hit points.", target_name, fighter.hp));
+            if fighter.hp < 1 {
+                results.push(format!("{} is dead.", target_name));
+                fighter.kill();
+                xp += fighter.xp_value;
+            }
+        } else {
+            results.push(format!("{} {} {}, but lacks the power to do anything useful.", instigator_name, verb, target_name));
+        }
     }
 
-    fn get_defense(&self) -> i32 { self.fighter.defense }
-    fn get_power(&self) -> i32 { self.fighter.power }
-    fn get_hp(&self) -> i32 { self.fighter.hp }
-    fn kill(&mut self) { self.fighter.dead = true; }
-    fn xp_value(&self)->i32 { self.fighter.xp_value }
-}
\ No newline at end of file
+    (xp, results)
+}
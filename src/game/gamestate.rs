@@ -1,22 +1,37 @@
-use super::{gui, TickType, inventory, Map, Player, map_builder, Combat, BaseEntity, GameState, rltk, player, mob, TileType, Particle, vfx};
-use rltk::{Rltk, RGB, Point};
+use super::{gui, TickType, Map, MAP_WIDTH, MAP_HEIGHT, Player, Fighter, Inventory, Viewshed, Name, map_builder, treasure_room, lighting, World, EntityId, GameState, rltk, TileType, LevelStore, Particle, vfx, input, session, GameRng, random, replay, spawner, systems, savegame, AiMaps, BuildLog};
+use rltk::{Rltk, Point, VirtualKeyCode, Algorithm2D};
 use serde::{Serialize, Deserialize};
-use std::fs;
-use std::fs::File;
-use std::io::Write;
-use std::path::Path;
+
+// Everyone starts with something to see by on the dark levels
+const STARTING_LIGHT : &str = "Lantern";
 
 #[derive(Serialize, Deserialize)]
 pub struct State {
     pub map : Map,
     pub game_state : TickType,
     pub log : Vec<String>,
-    pub entities : Vec<Box<BaseEntity>>,
+    pub world : World,
+    pub player_id : EntityId,
     pub target_cell : Point,
     pub targeting_item : i32,
     pub prev_mouse_for_targeting : Point,
+    #[serde(skip)]
     pub menu_state : gui::MenuState,
-    pub vfx : Vec<Particle>
+    #[serde(skip)]
+    pub vfx : Vec<Particle>,
+    pub seed : u64,
+    pub rng : GameRng,
+    pub levels : LevelStore,
+    #[serde(skip)]
+    pub save_slot : usize,
+    #[serde(skip)]
+    pub recorder : Option<replay::ReplayRecorder>,
+    #[serde(skip)]
+    pub replay : Option<replay::ReplayPlayer>,
+    #[serde(skip)]
+    pub ai_maps : Option<AiMaps>,
+    #[serde(skip)]
+    pub build_log : BuildLog
 }
 
 impl GameState for State {
@@ -24,158 +39,230 @@ impl GameState for State {
         vfx::age_particles(self, ctx);
         if self.game_state != TickType::MainMenu { gui::render(self, ctx, &self.map); }
 
+        if self.replay.is_some() {
+            replay::tick_replay(self, ctx);
+            return;
+        }
+
         match self.game_state {
             TickType::MainMenu => { 
                 let result = gui::display_main_menu(ctx, &mut self.menu_state); 
                 match result {
                     gui::MainMenuResult::Quit => { ctx.quit() }
-                    gui::MainMenuResult::Continue => {
-                        let saved = State::load_saved();
-                        self.map = saved.map;
-                        self.game_state = saved.game_state;
-                        self.log = saved.log;
-                        self.entities = saved.entities;
-                        self.target_cell = saved.target_cell;
-                        self.targeting_item = saved.targeting_item;
-                        self.prev_mouse_for_targeting = saved.prev_mouse_for_targeting;
+                    gui::MainMenuResult::Continue(slot) => {
+                        let saved = match savegame::load_game(slot) {
+                            Ok(saved) => saved,
+                            Err(e) => {
+                                self.menu_state.show_message(e.to_string());
+                                return;
+                            }
+                        };
+                        self.adopt(saved);
+                        self.save_slot = slot;
+                        self.recorder = Some(replay::ReplayRecorder::resume(&savegame::replay_file(slot)));
                     }
-                    gui::MainMenuResult::New => {
-                        let saved = State::new(0);
-                        self.map = saved.map;
-                        self.game_state = saved.game_state;
-                        self.log = saved.log;
-                        self.entities = saved.entities;
-                        self.target_cell = saved.target_cell;
-                        self.targeting_item = saved.targeting_item;
-                        self.prev_mouse_for_targeting = saved.prev_mouse_for_targeting;
+                    gui::MainMenuResult::New(slot, name) => {
+                        savegame::delete_save(slot);
+                        let seed = self.menu_state.seed.unwrap_or_else(random::random_seed);
+                        let mut game = State::new_game(seed);
+                        game.world.names.insert(game.player_id, Name{ name });
+                        self.adopt(game);
+                        self.save_slot = slot;
+                        self.recorder = replay::ReplayRecorder::start(&savegame::replay_file(slot), seed).ok();
                     }
                     _ => {}
                 }
             }
-            TickType::PlayersTurn => { 
-                let result = player::player_tick(self, ctx);
-                if result == player::PlayerTickResult::NextMap {
-                    // Move to next level
-                    self.player_mut().dungeon_level += 1;
-                    let mut saved = State::new(self.player().dungeon_level);
-                    saved.player_mut().copy_from_other_player(self.player());
-
-                    self.map = saved.map;
-                    self.entities = saved.entities;
-                    self.add_log_entry("You descend to the next level, and take a moment to rest.".to_string());
+            TickType::GameOver => { gui::display_game_over_and_handle_quit(ctx, self); }
+            TickType::PlayersTurn if ctx.key == Some(VirtualKeyCode::Escape) => {
+                match savegame::save_game(self, self.save_slot) {
+                    Ok(_) => {
+                        self.menu_state.refresh_slots();
+                        self.game_state = TickType::MainMenu;
+                    }
+                    Err(e) => { self.add_log_entry(format!("Unable to save: {}", e)); }
                 }
             }
-            TickType::EnemyTurn => {
-                mob::mob_tick(self);
-                self.game_state = TickType::PlayersTurn;
-                if self.player().fighter.dead { 
-                    self.game_state = TickType::GameOver; 
-                    if Path::new("./savegame.json").exists() { std::fs::remove_file("./savegame.json").expect("Unable to delete file"); } 
+            _ => {
+                if let Some(command) = input::read_command(self, ctx) {
+                    if let Some(recorder) = &self.recorder {
+                        if recorder.record(command).is_err() { self.recorder = None; }
+                    }
+                    session::apply_command(self, command);
+                    if self.game_state == TickType::GameOver {
+                        savegame::delete_save(self.save_slot);
+                        self.menu_state.refresh_slots();
+                    }
                 }
             }
-            TickType::GameOver => { gui::display_game_over_and_handle_quit(ctx, self); }
-            TickType::UseMenu => { inventory::use_item(self, ctx); }
-            TickType::DropMenu => { inventory::drop_item(self, ctx); }
-            TickType::WieldMenu => { inventory::wield_item(self, ctx); }
-            TickType::UnequipMenu => { inventory::unequip_item(self, ctx); }
-            TickType::TargetingItem => { inventory::item_targeting(self, ctx); }
-            TickType::LevelUpMenu => { gui::handle_level_up(ctx, self); }
-            TickType::CharacterMenu => { gui::display_character_info(ctx, self); }
-            TickType::HelpMenu => { gui::display_help_info(ctx, self); }
-            TickType::None => {}
         }
     }
 }
 
 impl State {
-    pub fn new_menu() -> State {        
+    // The front end's starting point; seed is used for the next new game, if given
+    pub fn new_menu(seed : Option<u64>) -> State {
+        let menu_seed = random::random_seed();
+        let rng = random::new_rng(menu_seed);
+        let mut menu_state = gui::MenuState::new(menu_seed);
+        menu_state.refresh_slots();
+        menu_state.seed = seed;
+
+        // The menu has no game, but State wants a player; it is replaced when a game starts
+        let mut world = World::new();
+        let player_id = spawner::player(&mut world, 0, 0);
+
         State{ 
-            map: Map::new(80, 43), 
+            map: Map::new(MAP_WIDTH, MAP_HEIGHT), 
             game_state: TickType::MainMenu, 
             log: Vec::new(), 
-            entities : Vec::new(),
+            world,
+            player_id,
             target_cell : Point::new(-1,-1),
             targeting_item : -1,
             prev_mouse_for_targeting : Point::new(-1,-1),
-            menu_state: gui::MenuState::new(),
-            vfx : Vec::new()
+            menu_state,
+            vfx : Vec::new(),
+            seed : menu_seed,
+            rng,
+            levels : LevelStore::new(),
+            save_slot : 0,
+            recorder : None,
+            replay : None,
+            ai_maps : None,
+            build_log : BuildLog::default()
         }
     }
 
-    pub fn load_saved() -> State {
-        let data = fs::read_to_string("./savegame.json").expect("Unable to read file");
-        let loaded : State = serde_json::from_str(&data).unwrap();
-        std::fs::remove_file("./savegame.json").expect("Unable to delete file");
-        loaded
+    // Takes over everything belonging to another game, just loaded or started, while
+    // keeping the front end's menu. Listing every field means a new one can't be
+    // forgotten here.
+    fn adopt(&mut self, other : State) {
+        let State{ map, game_state, log, world, player_id, target_cell, targeting_item, prev_mouse_for_targeting,
+            menu_state : _, vfx, seed, rng, levels, save_slot, recorder, replay, ai_maps, build_log } = other;
+        self.map = map;
+        self.game_state = game_state;
+        self.log = log;
+        self.world = world;
+        self.player_id = player_id;
+        self.target_cell = target_cell;
+        self.targeting_item = targeting_item;
+        self.prev_mouse_for_targeting = prev_mouse_for_targeting;
+        self.vfx = vfx;
+        self.seed = seed;
+        self.rng = rng;
+        self.levels = levels;
+        self.save_slot = save_slot;
+        self.recorder = recorder;
+        self.replay = replay;
+        self.ai_maps = ai_maps;
+        self.build_log = build_log;
     }
 
-    pub fn new(depth: i32) -> State {
-        if Path::new("./savegame.json").exists() { std::fs::remove_file("./savegame.json").expect("Unable to delete file"); }
-
-        let mut entities : Vec<Box<BaseEntity>> = Vec::new();
-        let mut map = Map::new(80, 43);
-        let rooms = map_builder::random_rooms_tut3(&mut map);
-        let (player_x, player_y) = rooms[0].center();
-        let mobs = map_builder::spawn_mobs(&rooms, depth);
-        let items = map_builder::spawn_items(&rooms, &mobs, depth);
-        let mut player = Player::new(player_x, player_y, 64, RGB::named(rltk::YELLOW));
-        let stairs_pos = rooms[rooms.len()-1].center();
-        map.tiles[((stairs_pos.1 * 80) + stairs_pos.0) as usize] = TileType::Stairs;
+    // Replays a recorded game in the front end, starting from its seed
+    pub fn new_replay(recorded : replay::Replay) -> State {
+        let mut gs = State::new_game(recorded.seed);
+        gs.replay = Some(replay::ReplayPlayer::new(recorded));
+        gs
+    }
 
-        // Start with a viewshed
-        player.plot_visibility(&map);
-        map.set_visibility(&player.visible_tiles);
+    pub fn new_game(seed : u64) -> State {
+        let rng = random::new_rng(seed);
+        let mut world = World::new();
+        let player_id = spawner::player(&mut world, 0, 0);
+        spawner::carried(&mut world, player_id, STARTING_LIGHT);
 
-        entities.push(Box::new(player));
-        for m in mobs {
-            entities.push(Box::new(m));
-        }
-        for i in items {
-            entities.push(Box::new(i));
-        }
-
-        State{ 
-            map, 
+        let mut gs = State{ 
+            map : Map::new(MAP_WIDTH, MAP_HEIGHT), 
             game_state: TickType::PlayersTurn, 
             log: Vec::new(), 
-            entities,
+            world,
+            player_id,
             target_cell : Point::new(-1,-1),
             targeting_item : -1,
             prev_mouse_for_targeting : Point::new(-1,-1),
-            menu_state : gui::MenuState::new(),
-            vfx : Vec::new()
+            menu_state : gui::MenuState::default(),
+            vfx : Vec::new(),
+            seed,
+            rng,
+            levels : LevelStore::new(),
+            save_slot : 0,
+            recorder : None,
+            replay : None,
+            ai_maps : None,
+            build_log : BuildLog::default()
+        };
+        gs.new_level(0);
+        gs
+    }
+
+    // Builds a fresh map for the given depth and populates it. The world should
+    // only hold the player (and what they carry) when this is called.
+    pub fn new_level(&mut self, depth : i32) {
+        let level = map_builder::build_level(MAP_WIDTH, MAP_HEIGHT, depth, &mut self.rng, &mut self.build_log);
+        let mut map = level.map;
+        self.world.positions.insert(self.player_id, level.start);
+        self.player_mut().dungeon_level = depth;
+        let start_idx = map.point2d_to_index(level.start) as usize;
+        if depth > 0 && map.tiles[start_idx] != TileType::Stairs {
+            // The way back up is where the player arrived
+            map.tiles[start_idx] = TileType::UpStairs;
         }
+        map.ambient_light = lighting::ambient_light(depth, &mut self.rng);
+        map_builder::spawn_mobs(&mut self.world, &level.spawn_regions, depth, &mut self.rng);
+        map_builder::spawn_items(&mut self.world, &level.spawn_regions, depth, &mut self.rng);
+        map_builder::spawn_traps(&mut self.world, &map, level.start, depth, &mut self.rng);
+        map_builder::spawn_torches(&mut self.world, &map, &mut self.rng);
+        for (pos, name) in level.fixed_spawns.iter() {
+            spawner::named(&mut self.world, pos.x, pos.y, name);
+        }
+        if let Some(treasure) = &level.treasure {
+            treasure_room::spawn_treasure(&mut self.world, treasure, depth, &mut self.rng);
+        }
+        self.map = map;
+
+        // Start with a viewshed
+        self.update_visibility();
     }
 
     pub fn player(&self) -> &Player {
-        self.entities[0].as_player().unwrap()
+        self.world.players.get(self.player_id).unwrap()
     }
 
     pub fn player_mut(&mut self) -> &mut Player {
-        self.entities[0].as_player_mut().unwrap()
+        self.world.players.get_mut(self.player_id).unwrap()
     }
 
-    pub fn player_as_combat(&mut self) -> &mut Combat {
-        self.entities[0].as_combat().unwrap()
+    pub fn player_position(&self) -> Point {
+        *self.world.positions.get(self.player_id).unwrap()
     }
 
-    pub fn update_visibility(&mut self) {
-        for e in self.entities.iter_mut() {
-            e.plot_visibility(&self.map);
-        }
+    pub fn player_fighter(&self) -> &Fighter {
+        self.world.fighters.get(self.player_id).unwrap()
+    }
+
+    pub fn player_fighter_mut(&mut self) -> &mut Fighter {
+        self.world.fighters.get_mut(self.player_id).unwrap()
+    }
 
-        let vt = self.player().visible_tiles.clone();
-        self.map.set_visibility(&vt);
+    pub fn player_inventory(&self) -> &Inventory {
+        self.world.inventories.get(self.player_id).unwrap()
+    }
+
+    pub fn player_inventory_mut(&mut self) -> &mut Inventory {
+        self.world.inventories.get_mut(self.player_id).unwrap()
+    }
+
+    pub fn player_viewshed(&self) -> &Viewshed {
+        self.world.viewsheds.get(self.player_id).unwrap()
+    }
+
+    pub fn update_visibility(&mut self) {
+        systems::visibility_system(self);
     }
 
     pub fn add_log_entry(&mut self, line : String) {
         self.log.insert(0, line.clone());
         while self.log.len() > 5 { self.log.remove(4); }
     }
-
-    pub fn save(&self) {
-        let data = serde_json::to_string(&self).unwrap();
-        let mut f = File::create("./savegame.json").expect("Unable to create file");
-        f.write_all(data.as_bytes()).expect("Unable to write data");
-    }
 }
\ No newline at end of file
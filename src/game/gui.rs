@@ -1,60 +1,80 @@
 use crate ::rltk;
 use crate ::rltk::Console;
 use rltk::{Rltk, Point, RGB, Algorithm2D, VirtualKeyCode};
-use super::{Map, TileType, State, TickType};
+use super::{Map, Camera, camera::{SCREEN_WIDTH, SCREEN_HEIGHT, VIEW_WIDTH, VIEW_HEIGHT}, TileType, State, TickType, LevelUpChoice, GameRng, random, EntityId, Renderable, savegame, savegame::SlotInfo, trap, noise, replay::ReplayPlayer};
 use std::cmp::{max, min};
-use serde::{Serialize, Deserialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 use rand::Rng;
-use std::path::Path;
 
 pub enum ItemMenuResult { Cancel, NoResponse, Selected }
 
+// How much of its colour a visible tile keeps with no light on it at all
+const MIN_SHADE : f32 = 0.2;
+
 pub fn render(gs : &State, ctx : &mut Rltk, map : &Map) {
-    draw_map(ctx, map);
-    draw_entities(gs, ctx, map);
+    let camera = Camera::new(gs);
+    draw_map(ctx, map, &camera);
+    draw_entities(gs, ctx, map, &camera);
     draw_user_interface(gs, ctx);
-    draw_mouse_info(gs, ctx, map);
+    draw_mouse_info(gs, ctx, map, &camera);
     for p in gs.vfx.iter() {
-        p.render(ctx);
+        p.render(ctx, &camera);
     }
 }
 
-fn draw_map(ctx : &mut Rltk, map : &Map) {
+fn draw_map(ctx : &mut Rltk, map : &Map, camera : &Camera) {
     ctx.cls();
 
-    let mut idx = 0;
-    for y in 0 .. map.height {
-        for x in 0 .. map.width {
+    for y in 0 .. VIEW_HEIGHT {
+        for x in 0 .. VIEW_WIDTH {
+            let pos = match camera.screen_to_world(x, y) {
+                Some(pos) if pos.x < map.width && pos.y < map.height => pos,
+                _ => { continue }
+            };
+            let idx = map.point2d_to_index(pos) as usize;
 
             // You wouldn't normally make this mess - clean up!
             if map.revealed[idx] {
                 if map.visible[idx] {
+                    let light = map.light[idx];
                     match map.tiles[idx] {
-                        TileType::Floor => { ctx.print_color(x, y, RGB::named(rltk::DARK_GREEN), RGB::named(rltk::BLACK), ".") }
-                        TileType::Wall => { ctx.set(x, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), decorate_wall_tile(map, Point::new(x,y))) }
-                        TileType::Stairs => { ctx.print_color(x, y, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), ">") }
+                        TileType::Floor => { ctx.print_color(x, y, lit(RGB::named(rltk::DARK_GREEN), light), RGB::named(rltk::BLACK), ".") }
+                        TileType::Wall => { ctx.set(x, y, lit(RGB::named(rltk::WHITE), light), RGB::named(rltk::BLACK), decorate_wall_tile(map, pos)) }
+                        TileType::Stairs => { ctx.print_color(x, y, lit(RGB::named(rltk::MAGENTA), light), RGB::named(rltk::BLACK), ">") }
+                        TileType::UpStairs => { ctx.print_color(x, y, lit(RGB::named(rltk::MAGENTA), light), RGB::named(rltk::BLACK), "<") }
+                        TileType::ClosedDoor => { ctx.print_color(x, y, lit(RGB::named(rltk::CHOCOLATE), light), RGB::named(rltk::BLACK), "+") }
+                        TileType::OpenDoor => { ctx.print_color(x, y, lit(RGB::named(rltk::CHOCOLATE), light), RGB::named(rltk::BLACK), "'") }
+                        TileType::LockedDoor(_) => { ctx.print_color(x, y, lit(RGB::named(rltk::GOLD), light), RGB::named(rltk::BLACK), "+") }
                     }
                 } else {
                     match map.tiles[idx] {
                         TileType::Floor => { ctx.print_color(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), ".") }
-                        TileType::Wall => { ctx.set(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), decorate_wall_tile(map, Point::new(x,y))) }
+                        TileType::Wall => { ctx.set(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), decorate_wall_tile(map, pos)) }
                         TileType::Stairs => { ctx.print_color(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), ">") }
+                        TileType::UpStairs => { ctx.print_color(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "<") }
+                        TileType::ClosedDoor => { ctx.print_color(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "+") }
+                        TileType::OpenDoor => { ctx.print_color(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "'") }
+                        TileType::LockedDoor(_) => { ctx.print_color(x, y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "+") }
                     }
                 }
             }
-
-            idx += 1;
         }
     }
 }
 
+// Shades a colour by the light falling on it
+fn lit(colour : RGB, light : RGB) -> RGB {
+    let shade = |channel : f32| f32::min(1.0, MIN_SHADE + channel);
+    RGB::from_f32(colour.r * shade(light.r), colour.g * shade(light.g), colour.b * shade(light.b))
+}
+
 fn is_revealed_and_wall(map : &Map, coord: Point) -> bool {
     let idx = map.point2d_to_index(coord) as usize;
     map.tiles[idx] == TileType::Wall && map.revealed[idx]
 }
 
 fn decorate_wall_tile(map : &Map, coord: Point) -> u8 {
-    if coord.x == 0 || coord.x == map.width || coord.y == 0 || coord.y == map.height { return 35; }
+    if coord.x == 0 || coord.x >= map.width - 1 || coord.y == 0 || coord.y >= map.height - 1 { return 35; }
     let mut mask : u8 = 0;
     if is_revealed_and_wall(map, Point::new(coord.x, coord.y - 1)) { mask += 1; }
     if is_revealed_and_wall(map, Point::new(coord.x, coord.y + 1)) { mask += 2; }
@@ -81,43 +101,69 @@ fn decorate_wall_tile(map : &Map, coord: Point) -> u8 {
     }
 }
 
-fn draw_entities(gs: &State, ctx: &mut Rltk, map : &Map) {
-    for e in gs.entities.iter() {
-            e.draw_to_map(ctx, &map);
+fn draw_entities(gs: &State, ctx: &mut Rltk, map : &Map, camera : &Camera) {
+    let mut to_draw : Vec<(Point, &Renderable)> = Vec::new();
+    for (id, renderable) in gs.world.renderables.iter() {
+        if let Some(pos) = gs.world.positions.get(id) {
+            if !map.is_tile_visible(*pos) || trap::is_hidden(gs, id) { continue; }
+            if let Some(screen_pos) = camera.world_to_screen(*pos) { to_draw.push((screen_pos, renderable)); }
         }
+    }
+
+    // Highest render order first, so the lowest ends up on top
+    to_draw.sort_by_key(|d| -d.1.render_order);
+    for (pos, renderable) in to_draw {
+        ctx.set(pos.x, pos.y, renderable.fg, RGB::named(rltk::BLACK), renderable.glyph);
+    }
+}
+
+fn tooltip_text(gs : &State, id : EntityId) -> String {
+    if id == gs.player_id { "It's you!".to_string() }
+    else if let Some(mob) = gs.world.mobs.get(id) { format!("Enemy: {} ({})", gs.world.name(id), mob.state.describe()) }
+    else if gs.world.traps.contains(id) { format!("Trap: {}", gs.world.name(id)) }
+    else if gs.world.items.contains(id) { format!("Item: {}", gs.world.name(id)) }
+    else { gs.world.name(id) }
 }
 
 fn draw_user_interface(gs: &State, ctx : &mut Rltk) {
     let mouse_pos = ctx.mouse_pos();
     ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::MAGENTA));
-    ctx.draw_box(1, 43, 78, 6, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+    ctx.draw_box(1, VIEW_HEIGHT, SCREEN_WIDTH - 2, SCREEN_HEIGHT - VIEW_HEIGHT - 1, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
 
     let maplvl = format!("Depth: {} ", gs.player().dungeon_level);
-    ctx.print_color(3, 43, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &maplvl);
+    ctx.print_color(3, VIEW_HEIGHT, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &maplvl);
 
-    let health = format!(" HP: {} / {} ", gs.player().fighter.hp, gs.player().fighter.max_hp);
-    ctx.print_color(12, 43, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &health);
+    let fighter = gs.player_fighter();
+    let health = format!(" HP: {} / {} ", fighter.hp, fighter.max_hp);
+    ctx.print_color(12, VIEW_HEIGHT, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &health);
 
-    ctx.draw_bar_horizontal(28, 43, 51, gs.player().fighter.hp, gs.player().fighter.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+    ctx.draw_bar_horizontal(28, VIEW_HEIGHT, 51, fighter.hp, fighter.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+    if gs.player().sneaking {
+        ctx.print_color(3, SCREEN_HEIGHT - 1, RGB::named(rltk::GREEN), RGB::named(rltk::BLACK), " Sneaking ");
+    }
 
-    let mut y = 44;
+    let mut y = VIEW_HEIGHT + 1;
     for s in gs.log.iter() {
         ctx.print(2, y, &s.to_string());
         y += 1;
     }
 }
 
-fn draw_mouse_info(gs : &State, ctx : &mut Rltk, map: &Map) {
+fn draw_mouse_info(gs : &State, ctx : &mut Rltk, map: &Map, camera : &Camera) {
     let mouse_pos = ctx.mouse_pos();
-    if map.is_tile_visible(Point::new(mouse_pos.0, mouse_pos.1)) {
+    let map_pos = match camera.screen_to_world(mouse_pos.0, mouse_pos.1) {
+        None => { return }
+        Some(pos) => pos
+    };
+    if map.is_tile_visible(map_pos) {
         let mut tooltip : Vec<String> = Vec::new();
 
-        let tile_info = map.tile_description(Point::new(mouse_pos.0, mouse_pos.1));
+        let tile_info = map.tile_description(map_pos);
         tooltip.push(format!("Tile: {}", tile_info));
 
-        for e in gs.entities.iter() {
-            if e.get_position() == Point::new(mouse_pos.0, mouse_pos.1) {
-                tooltip.push(e.get_tooltip_text());
+        for (id, pos) in gs.world.positions.iter() {
+            if *pos == map_pos && gs.world.renderables.contains(id) && !trap::is_hidden(gs, id) {
+                tooltip.push(tooltip_text(gs, id));
             }
         }
 
@@ -128,7 +174,7 @@ fn draw_mouse_info(gs : &State, ctx : &mut Rltk, map: &Map) {
             }
             width += 3;
 
-            if mouse_pos.0 > 40 {
+            if mouse_pos.0 > SCREEN_WIDTH / 2 {
                 let arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
                 let left_x = mouse_pos.0 - width;
                 let mut y = mouse_pos.1;
@@ -161,18 +207,18 @@ fn draw_mouse_info(gs : &State, ctx : &mut Rltk, map: &Map) {
 
 #[allow(non_snake_case)]
 pub fn handle_item_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, title: S) -> (ItemMenuResult, i32) {
-    let count = gs.player().inventory.items.len();
+    let count = gs.player_inventory().items.len();
     let mut y = (25 - (count / 2)) as i32;
 
     ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &title.to_string());
 
-    for (j,i) in gs.player().inventory.items.iter().enumerate() {
+    for (j,i) in gs.player_inventory().items.iter().enumerate() {
         ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), 40);
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as u8);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), 41);
 
-        ctx.print(21, y, &i.name.to_string());
+        ctx.print(21, y, &gs.world.name(*i));
         y += 1;
     }
 
@@ -183,7 +229,7 @@ pub fn handle_item_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, title: S)
                 VirtualKeyCode::Escape => { return (ItemMenuResult::Cancel, 0) }
                 _ => { 
                     let selection = rltk::letter_to_option(KEY);
-                    if selection > -1 && selection < gs.player().inventory.items.len() as i32 {
+                    if selection > -1 && selection < gs.player_inventory().items.len() as i32 {
                         return (ItemMenuResult::Selected, selection);
                     }  
                     return (ItemMenuResult::NoResponse, 0);
@@ -197,7 +243,7 @@ pub fn handle_item_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, title: S)
 
 #[allow(non_snake_case)]
 pub fn handle_equippable_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, title: S) -> (ItemMenuResult, i32) {
-    let equippable = gs.player().inventory.get_equippable_items();
+    let equippable = gs.player_inventory().get_equippable_items(&gs.world);
     let count = equippable.len();
     let mut y = (25 - (count / 2)) as i32;
 
@@ -209,7 +255,7 @@ pub fn handle_equippable_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, titl
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as u8);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), 41);
 
-        ctx.print(21, y, &gs.player().inventory.items[*i as usize].name.to_string());
+        ctx.print(21, y, &gs.world.name(gs.player_inventory().items[*i as usize]));
         y += 1;
     }
 
@@ -220,7 +266,7 @@ pub fn handle_equippable_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, titl
                 VirtualKeyCode::Escape => { return (ItemMenuResult::Cancel, 0) }
                 _ => { 
                     let selection = rltk::letter_to_option(KEY);
-                    if selection > -1 && selection < gs.player().inventory.items.len() as i32 {
+                    if selection > -1 && selection < equippable.len() as i32 {
                         return (ItemMenuResult::Selected, equippable[selection as usize]);
                     }  
                     return (ItemMenuResult::NoResponse, 0);
@@ -234,18 +280,18 @@ pub fn handle_equippable_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, titl
 
 #[allow(non_snake_case)]
 pub fn handle_equipped_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, title: S) -> (ItemMenuResult, i32) {
-    let count = gs.player().inventory.equipped.len();
+    let count = gs.player_inventory().equipped.len();
     let mut y = (25 - (count / 2)) as i32;
 
     ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &title.to_string());
 
-    for (j,i) in gs.player().inventory.equipped.iter().enumerate() {
+    for (j,i) in gs.player_inventory().equipped.iter().enumerate() {
         ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), 40);
         ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as u8);
         ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), 41);
 
-        ctx.print(21, y, &i.name.to_string());
+        ctx.print(21, y, &gs.world.name(*i));
         y += 1;
     }
 
@@ -256,7 +302,7 @@ pub fn handle_equipped_menu<S: ToString>(gs : &mut State, ctx: &mut Rltk, title:
                 VirtualKeyCode::Escape => { return (ItemMenuResult::Cancel, 0) }
                 _ => { 
                     let selection = rltk::letter_to_option(KEY);
-                    if selection > -1 && selection < gs.player().inventory.equipped.len() as i32 {
+                    if selection > -1 && selection < gs.player_inventory().equipped.len() as i32 {
                         return (ItemMenuResult::Selected, selection);
                     }  
                     return (ItemMenuResult::NoResponse, 0);
@@ -278,21 +324,26 @@ pub fn display_game_over_and_handle_quit(ctx : &mut Rltk, gs : &mut State) {
 #[allow(non_snake_case)]
 pub fn handle_item_targeting<S: ToString>(gs : &mut State, ctx: &mut Rltk, title: S) -> ItemMenuResult {
     ctx.print_color(0,0, RGB::named(rltk::YELLOW), RGB::named(rltk::RED), &title.to_string());
+    let camera = Camera::new(gs);
     let mouse_tuple = ctx.mouse_pos();
     let mouse_pos = Point::new(mouse_tuple.0, mouse_tuple.1);
     let previous_mouse = gs.prev_mouse_for_targeting;
 
-    if mouse_pos != previous_mouse && mouse_pos.x > 0 && mouse_pos.x < 79 && mouse_pos.y > 0 && mouse_pos.y < 40 { gs.target_cell = mouse_pos; }
+    // The mouse only takes over from the keyboard when it moves
+    if mouse_pos != previous_mouse {
+        if let Some(map_pos) = camera.screen_to_world(mouse_pos.x, mouse_pos.y) { gs.target_cell = map_pos; }
+        gs.prev_mouse_for_targeting = mouse_pos;
+    }
 
-    if gs.target_cell.x < 1 { gs.target_cell.x = 1; }
-    if gs.target_cell.x > 79 { gs.target_cell.x = 79; }
-    if gs.target_cell.y < 1 { gs.target_cell.y = 1; }
-    if gs.target_cell.y > 39 { gs.target_cell.y = 39; }
+    let (max_x, max_y) = (gs.map.width - 2, gs.map.height - 2);
+    gs.target_cell = Point::new(min(max(gs.target_cell.x, 1), max_x), min(max(gs.target_cell.y, 1), max_y));
 
     let possible = gs.map.is_tile_visible(gs.target_cell);
 
     if possible {
-        ctx.set_bg(gs.target_cell.x, gs.target_cell.y, RGB::named(rltk::RED));
+        if let Some(screen_pos) = camera.world_to_screen(gs.target_cell) {
+            ctx.set_bg(screen_pos.x, screen_pos.y, RGB::named(rltk::RED));
+        }
         if ctx.left_click {
             return ItemMenuResult::Selected;
         }
@@ -306,17 +357,17 @@ pub fn handle_item_targeting<S: ToString>(gs : &mut State, ctx: &mut Rltk, title
                 VirtualKeyCode::Return => { if possible { return ItemMenuResult::Selected } }
                 VirtualKeyCode::Space => { if possible { return ItemMenuResult::Selected } }
                 VirtualKeyCode::Left => { gs.target_cell.x = max(gs.target_cell.x-1, 1) }
-                VirtualKeyCode::Right => { gs.target_cell.x = min(gs.target_cell.x+1, 79) }
+                VirtualKeyCode::Right => { gs.target_cell.x = min(gs.target_cell.x+1, max_x) }
                 VirtualKeyCode::Up => { gs.target_cell.y = max(gs.target_cell.y-1, 1) }
-                VirtualKeyCode::Down => { gs.target_cell.y = min(gs.target_cell.y+1, 40) }
+                VirtualKeyCode::Down => { gs.target_cell.y = min(gs.target_cell.y+1, max_y) }
                 VirtualKeyCode::Numpad4 => { gs.target_cell.x = max(gs.target_cell.x-1, 1) }
-                VirtualKeyCode::Numpad6 => { gs.target_cell.x = min(gs.target_cell.x+1, 79) }
+                VirtualKeyCode::Numpad6 => { gs.target_cell.x = min(gs.target_cell.x+1, max_x) }
                 VirtualKeyCode::Numpad8 => { gs.target_cell.y = max(gs.target_cell.y-1, 1) }
-                VirtualKeyCode::Numpad2 => { gs.target_cell.y = min(gs.target_cell.y+1, 40) }
+                VirtualKeyCode::Numpad2 => { gs.target_cell.y = min(gs.target_cell.y+1, max_y) }
                 VirtualKeyCode::Numpad7 => { gs.target_cell = Point::new(  max(gs.target_cell.x-1, 1), max(gs.target_cell.y-1, 1) ) }
-                VirtualKeyCode::Numpad9 => { gs.target_cell = Point::new(  min(gs.target_cell.x+1, 79), max(gs.target_cell.y-1, 1) ) }
-                VirtualKeyCode::Numpad1 => { gs.target_cell = Point::new(  max(gs.target_cell.x-1, 1), min(gs.target_cell.y+1, 40) ) }
-                VirtualKeyCode::Numpad3 => { gs.target_cell = Point::new(  min(gs.target_cell.x+1, 79), min(gs.target_cell.y+1, 40) ) }
+                VirtualKeyCode::Numpad9 => { gs.target_cell = Point::new(  min(gs.target_cell.x+1, max_x), max(gs.target_cell.y-1, 1) ) }
+                VirtualKeyCode::Numpad1 => { gs.target_cell = Point::new(  max(gs.target_cell.x-1, 1), min(gs.target_cell.y+1, max_y) ) }
+                VirtualKeyCode::Numpad3 => { gs.target_cell = Point::new(  min(gs.target_cell.x+1, max_x), min(gs.target_cell.y+1, max_y) ) }
                 _ => { }
             }
         }
@@ -325,26 +376,39 @@ pub fn handle_item_targeting<S: ToString>(gs : &mut State, ctx: &mut Rltk, title
     ItemMenuResult::NoResponse
 }
 
+pub fn draw_replay_status(ctx : &mut Rltk, player : &ReplayPlayer) {
+    let status = if player.finished() { "finished" } else if player.paused { "paused" } else if player.fast_forward { "fast-forward" } else { "playing" };
+    let line = format!("REPLAY {}/{} ({}) - Space: pause, S: step, F: fast-forward, ESC: quit", player.position, player.replay.commands.len(), status);
+    ctx.print_color(0, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLUE), &line);
+}
+
 const STORY_TYPES : & [& str] = &["Tales", "Sagas", "Adventures", "Anecdotes", "Fables", "Narratives"];
 const STORY_NOUNS : & [& str] = &["Heroism", "Cowardice", "Vengeance", "Heroism", "Exploration", "Delving", "Dungeoneering"];
 
-#[derive(Serialize, Deserialize)]
+const MAX_NAME_LENGTH : usize = 20;
+const DEFAULT_NAME : &str = "Adventurer";
+const SLOT_LIST_Y : i32 = 21;
+
+enum MenuMode { Slots, NameEntry{ slot : usize, name : String }, ConfirmDelete(usize) }
+
+// Front-end only; it isn't part of a saved game
 pub struct MenuState {
     random : Vec<usize>,
-    save_exists : bool,
-    current_menu_option : i32,
-    backdrop : Vec<(u8, f32)>
+    slots : Vec<SlotInfo>,
+    selected_slot : usize,
+    mode : MenuMode,
+    backdrop : Vec<(u8, f32)>,
+    message : Option<String>,
+    pub seed : Option<u64>,
+    // The backdrop's own randomness, kept apart from the game's seeded stream
+    rng : GameRng
 }
 
 impl MenuState {
-    pub fn new() -> MenuState {
-        let mut rng = rand::thread_rng();
-        let save_exists = Path::new("./savegame.json").exists();
-        let mut cmo = 1;
-        if save_exists { cmo = 0; }
-
+    pub fn new(menu_seed : u64) -> MenuState {
+        let mut rng = random::new_rng(menu_seed);
         let mut bd : Vec<(u8, f32)> = Vec::new();
-        for _i in 0..(80*50) {
+        for _i in 0..(SCREEN_WIDTH * SCREEN_HEIGHT) {
             let bg_i = rng.gen_range(0, 192);
             let bg : f32 = bg_i as f32 / 255.0;
             bd.push((rng.gen_range(32, 62) as u8, bg));
@@ -352,45 +416,73 @@ impl MenuState {
 
         MenuState{
             random: vec![rng.gen_range(0, 6), rng.gen_range(0, 7), rng.gen_range(0, 7)],
-            save_exists,
-            current_menu_option : cmo,
-            backdrop : bd
+            slots : Vec::new(),
+            selected_slot : 0,
+            mode : MenuMode::Slots,
+            backdrop : bd,
+            message : None,
+            seed : None,
+            rng
+        }
+    }
+
+    // Re-reads the save directory, e.g. after a game was saved, lost or deleted
+    pub fn refresh_slots(&mut self) {
+        self.slots = savegame::list_slots();
+        self.mode = MenuMode::Slots;
+        if self.selected_slot >= self.slots.len() { self.selected_slot = 0; }
+    }
+
+    // Shown under the menu, e.g. when a saved game couldn't be loaded
+    pub fn show_message(&mut self, message : String) {
+        self.message = Some(message);
+    }
+}
+
+impl Default for MenuState {
+    fn default() -> MenuState {
+        MenuState{
+            random : vec![0, 0, 0],
+            slots : Vec::new(),
+            selected_slot : 0,
+            mode : MenuMode::Slots,
+            backdrop : vec![(32, 0.0); (SCREEN_WIDTH * SCREEN_HEIGHT) as usize],
+            message : None,
+            seed : None,
+            rng : random::new_rng(0)
         }
     }
 }
 
-pub enum MainMenuResult { None, Continue, New, Quit }
+pub enum MainMenuResult { None, Continue(usize), New(usize, String), Quit }
 
-#[allow(non_snake_case)]
 pub fn display_main_menu(ctx : &mut Rltk, ms : &mut MenuState) -> MainMenuResult {
-    let mut rng = rand::thread_rng();
     ctx.cls();
 
     // Backdrop
-    for y in 0..50 {
-        for x in 0..80 {
-            let idx = (y*80)+x;
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let idx = (y*SCREEN_WIDTH)+x;
             ctx.set(x, y, RGB::from_f32(0.0, ms.backdrop[idx as usize].1, 0.0), RGB::named(rltk::BLACK), ms.backdrop[idx as usize].0);
         }
     }
 
-    for x in 0..80 {
-        for y in (1..50).rev() {
-            let idx = (y * 80) + x;
-            let above_idx = ((y-1) * 80) + x;
+    for x in 0..SCREEN_WIDTH {
+        for y in (1..SCREEN_HEIGHT).rev() {
+            let idx = ((y * SCREEN_WIDTH) + x) as usize;
+            let above_idx = (((y-1) * SCREEN_WIDTH) + x) as usize;
             ms.backdrop[idx] = ms.backdrop[above_idx];
             ms.backdrop[idx].1 -= 0.02;
             if ms.backdrop[idx].1 < 0.0 {
-                let bg_i = rng.gen_range(0, 192);
+                let bg_i = ms.rng.gen_range(0, 192);
                 let bg : f32 = bg_i as f32 / 255.0;
-                ms.backdrop[idx] = (rng.gen_range(32, 62) as u8, bg);
+                ms.backdrop[idx] = (ms.rng.gen_range(32, 62) as u8, bg);
             }
         }
-        let y = 0;
-        let idx = (y * 80) + x;
-        let bg_i = rng.gen_range(0, 192);
+        let idx = x as usize;
+        let bg_i = ms.rng.gen_range(0, 192);
         let bg : f32 = bg_i as f32 / 255.0;
-        ms.backdrop[idx] = (rng.gen_range(32, 62) as u8, bg);
+        ms.backdrop[idx] = (ms.rng.gen_range(32, 62) as u8, bg);
     }
 
     // Header
@@ -398,26 +490,9 @@ pub fn display_main_menu(ctx : &mut Rltk, ms : &mut MenuState) -> MainMenuResult
     ctx.print_color_centered(10, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Rusty Roguelike v1.0");
     ctx.print_color_centered(12, RGB::named(rltk::RED), RGB::named(rltk::BLACK), &format!("{} in {} and {}", STORY_TYPES[ms.random[0]], STORY_NOUNS[ms.random[1]], STORY_NOUNS[ms.random[2]]));
 
-    // Menu render
-    let mut y = 15;
-    if ms.save_exists {
-        if ms.current_menu_option == 0 {
-            ctx.print_color_centered(y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "(C)ontinue Saved Game");
-        } else {
-            ctx.print_color_centered(y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "(C)ontinue Saved Game");
-        }
-        y += 1;
-    }
-    if ms.current_menu_option == 1 {
-        ctx.print_color_centered(y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "(N)ew Game");
-    } else {
-        ctx.print_color_centered(y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "(N)ew Game");
-    }
-    y += 1;
-    if ms.current_menu_option == 2 {
-        ctx.print_color_centered(y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "(Q)uit");
-    } else {
-        ctx.print_color_centered(y, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "(Q)uit");
+    if let Some(message) = &ms.message {
+        let shown : String = message.chars().take(78).collect();
+        ctx.print_color_centered(SLOT_LIST_Y + 10, RGB::named(rltk::RED), RGB::named(rltk::BLACK), &shown);
     }
 
     // Copyright blurb
@@ -425,34 +500,132 @@ pub fn display_main_menu(ctx : &mut Rltk, ms : &mut MenuState) -> MainMenuResult
     ctx.print_color_centered(43, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "https://github.com/thebracket/rustyroguelike");
     ctx.print_color_centered(44, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), "(c) 2019 Bracket Productions");
 
-    // Keyboard input
+    match ms.mode {
+        MenuMode::Slots => { slot_menu(ctx, ms) }
+        MenuMode::NameEntry{ .. } => { name_entry(ctx, ms) }
+        MenuMode::ConfirmDelete(slot) => { confirm_delete(ctx, ms, slot); MainMenuResult::None }
+    }
+}
+
+fn draw_slot_list(ctx : &mut Rltk, ms : &MenuState) {
+    for (i, slot) in ms.slots.iter().enumerate() {
+        let line = format!("{}. {}", i + 1, slot_description(slot));
+        let colour = if i == ms.selected_slot { RGB::named(rltk::YELLOW) } else { RGB::named(rltk::GREY) };
+        ctx.print_color(12, SLOT_LIST_Y + i as i32, colour, RGB::named(rltk::BLACK), &line);
+    }
+}
+
+fn slot_description(slot : &SlotInfo) -> String {
+    match slot {
+        SlotInfo::Empty => "(empty)".to_string(),
+        SlotInfo::Saved(summary) => format!("{}, level {}, depth {} - {}", summary.name, summary.level, summary.depth, last_played_text(summary.last_played)),
+        SlotInfo::Unreadable(_) => "(damaged save)".to_string()
+    }
+}
+
+fn last_played_text(last_played : u64) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let minutes = now.saturating_sub(last_played) / 60;
+    let (count, unit) = if minutes < 1 { return "just now".to_string() }
+        else if minutes < 60 { (minutes, "minute") }
+        else if minutes < 60 * 24 { (minutes / 60, "hour") }
+        else { (minutes / (60 * 24), "day") };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+#[allow(non_snake_case)]
+fn slot_menu(ctx : &mut Rltk, ms : &mut MenuState) -> MainMenuResult {
+    draw_slot_list(ctx, ms);
+    ctx.print_color_centered(SLOT_LIST_Y + 7, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "ENTER: play  (N)ew game  (D)elete  (Q)uit");
+
+    let start_new = |ms : &mut MenuState| {
+        ms.message = None;
+        ms.mode = MenuMode::NameEntry{ slot: ms.selected_slot, name: String::new() };
+    };
+
     match ctx.key {
         None => {}
         Some(KEY) => {
             match KEY {
                 VirtualKeyCode::Escape => { return MainMenuResult::Quit }
                 VirtualKeyCode::Q => { return MainMenuResult::Quit }
-                VirtualKeyCode::N => { return MainMenuResult::New }
-                VirtualKeyCode::C => { if ms.save_exists { return MainMenuResult::Continue } }
+                VirtualKeyCode::N => { start_new(ms) }
+                VirtualKeyCode::D => {
+                    match ms.slots.get(ms.selected_slot) {
+                        None | Some(SlotInfo::Empty) => {}
+                        Some(_) => { ms.mode = MenuMode::ConfirmDelete(ms.selected_slot) }
+                    }
+                }
+                // Wrapping round, and staying put if there are no slots at all
                 VirtualKeyCode::Up => {
-                    ms.current_menu_option -= 1;
-                    if ms.save_exists && ms.current_menu_option < 0 { ms.current_menu_option = 2 }
-                    if (!ms.save_exists) && ms.current_menu_option < 1 { ms.current_menu_option = 1 }
+                    ms.selected_slot = if ms.selected_slot == 0 { ms.slots.len().saturating_sub(1) } else { ms.selected_slot - 1 };
                 }
                 VirtualKeyCode::Down => {
-                    ms.current_menu_option += 1;
-                    if ms.save_exists && ms.current_menu_option > 2 { ms.current_menu_option = 0 }
-                    if (!ms.save_exists) && ms.current_menu_option > 2 { ms.current_menu_option = 1 }
+                    ms.selected_slot = (ms.selected_slot + 1).checked_rem(ms.slots.len()).unwrap_or(0);
                 }
                 VirtualKeyCode::Return => {
-                    match ms.current_menu_option {
-                        0 => { return MainMenuResult::Continue }
-                        1 => { return MainMenuResult::New }
-                        2 => { return MainMenuResult::Quit }
-                        _ => {}
+                    match ms.slots.get(ms.selected_slot) {
+                        None => {}
+                        Some(SlotInfo::Empty) => { start_new(ms) }
+                        Some(_) => { return MainMenuResult::Continue(ms.selected_slot) }
                     }
                 }
-                _ => {}
+                _ => {
+                    if let Some(slot) = number_key(KEY) {
+                        if slot < ms.slots.len() { ms.selected_slot = slot; }
+                    }
+                }
+            }
+        }
+    }
+
+    MainMenuResult::None
+}
+
+fn number_key(key : VirtualKeyCode) -> Option<usize> {
+    match key {
+        VirtualKeyCode::Key1 => Some(0),
+        VirtualKeyCode::Key2 => Some(1),
+        VirtualKeyCode::Key3 => Some(2),
+        VirtualKeyCode::Key4 => Some(3),
+        VirtualKeyCode::Key5 => Some(4),
+        VirtualKeyCode::Key6 => Some(5),
+        VirtualKeyCode::Key7 => Some(6),
+        VirtualKeyCode::Key8 => Some(7),
+        VirtualKeyCode::Key9 => Some(8),
+        _ => None
+    }
+}
+
+// Typing a name for a new character. There's no shift key to read, so words are capitalised for you.
+fn name_entry(ctx : &mut Rltk, ms : &mut MenuState) -> MainMenuResult {
+    let (slot, name) = match &mut ms.mode {
+        MenuMode::NameEntry{ slot, name } => (*slot, name),
+        _ => { return MainMenuResult::None }
+    };
+
+    ctx.print_color(12, SLOT_LIST_Y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Name your character (slot {}):", slot + 1));
+    ctx.print_color(12, SLOT_LIST_Y + 2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &format!("{}_", name));
+    if let Some(SlotInfo::Saved(summary)) = ms.slots.get(slot) {
+        ctx.print_color(12, SLOT_LIST_Y + 4, RGB::named(rltk::RED), RGB::named(rltk::BLACK), &format!("This replaces {}'s saved game.", summary.name));
+    }
+    ctx.print_color_centered(SLOT_LIST_Y + 7, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "ENTER to begin, ESCAPE to go back");
+
+    match ctx.key {
+        None => {}
+        Some(VirtualKeyCode::Escape) => { ms.mode = MenuMode::Slots; }
+        Some(VirtualKeyCode::Return) => {
+            let name = name.trim().to_string();
+            let name = if name.is_empty() { DEFAULT_NAME.to_string() } else { name };
+            return MainMenuResult::New(slot, name);
+        }
+        Some(VirtualKeyCode::Back) => { name.pop(); }
+        Some(VirtualKeyCode::Space) if !name.is_empty() && !name.ends_with(' ') && name.len() < MAX_NAME_LENGTH => { name.push(' '); }
+        Some(key) => {
+            let letter = rltk::letter_to_option(key);
+            if letter >= 0 && name.len() < MAX_NAME_LENGTH {
+                let c = (b'a' + letter as u8) as char;
+                if name.is_empty() || name.ends_with(' ') { name.push(c.to_ascii_uppercase()); } else { name.push(c); }
             }
         }
     }
@@ -460,8 +633,23 @@ pub fn display_main_menu(ctx : &mut Rltk, ms : &mut MenuState) -> MainMenuResult
     MainMenuResult::None
 }
 
+fn confirm_delete(ctx : &mut Rltk, ms : &mut MenuState, slot : usize) {
+    draw_slot_list(ctx, ms);
+    ctx.print_color_centered(SLOT_LIST_Y + 7, RGB::named(rltk::RED), RGB::named(rltk::BLACK), &format!("Delete slot {}? It can't be undone. (Y)es / (N)o", slot + 1));
+
+    match ctx.key {
+        Some(VirtualKeyCode::Y) => {
+            savegame::delete_save(slot);
+            ms.message = None;
+            ms.refresh_slots();
+        }
+        Some(VirtualKeyCode::N) | Some(VirtualKeyCode::Escape) => { ms.mode = MenuMode::Slots; }
+        _ => {}
+    }
+}
+
 #[allow(non_snake_case)]
-pub fn handle_level_up(ctx : &mut Rltk, gs : &mut State) {
+pub fn handle_level_up(ctx : &mut Rltk, gs : &State) -> Option<LevelUpChoice> {
 
     ctx.draw_box(10, 8, 60, 18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     ctx.print_color_centered(10, RGB::named(rltk::WHITE), RGB::named(rltk::RED), &format!("Congratulations, you are now level {}!", gs.player().level));
@@ -475,67 +663,58 @@ pub fn handle_level_up(ctx : &mut Rltk, gs : &mut State) {
         None => {}
         Some(KEY) => {
             match KEY {
-                VirtualKeyCode::A => { 
-                    gs.player_mut().fighter.max_hp += 10;
-                    gs.player_mut().fighter.hp = gs.player().fighter.max_hp;
-                    gs.game_state = TickType::PlayersTurn;
-                }
-                VirtualKeyCode::B => { 
-                    gs.player_mut().fighter.power += 1;
-                    gs.game_state = TickType::PlayersTurn;
-                }
+                VirtualKeyCode::A => { return Some(LevelUpChoice::HitPoints) }
+                VirtualKeyCode::B => { return Some(LevelUpChoice::Power) }
                 _ => {}
             }
         }
     }
+
+    None
 }
 
 #[allow(non_snake_case)]
-pub fn display_character_info(ctx : &mut Rltk, gs : &mut State) {
+pub fn display_character_info(ctx : &mut Rltk, gs : &State) -> bool {
     let player = gs.player();
+    let fighter = gs.player_fighter();
     ctx.draw_box(10, 8, 60, 16, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     ctx.print_color_centered(10, RGB::named(rltk::WHITE), RGB::named(rltk::RED), "Character Information");
     ctx.print_color_centered(12, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "You are not dead yet. That's something.");
     ctx.print_color_centered(13, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("You have beaten {} dungeon levels.", player.dungeon_level));
     ctx.print_color_centered(14, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("You have {} experience points, needing {} to level.", player.xp, player.xp_to_level()));
     ctx.print_color_centered(15, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("You are level {}.", player.level));
-    ctx.print_color_centered(16, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("You have {} hit points, out of {}.", player.fighter.hp, player.fighter.max_hp));
-    ctx.print_color_centered(17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Your hit power is {}.", player.fighter.power));
-    ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Your defense power is {}.", player.fighter.defense));
+    ctx.print_color_centered(16, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("You have {} hit points, out of {}.", fighter.hp, fighter.max_hp));
+    ctx.print_color_centered(17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Your hit power is {}.", fighter.power));
+    ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Your defense power is {}.", fighter.defense));
+    ctx.print_color_centered(19, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), &format!("Your stealth is {}.", noise::player_stealth(gs)));
+    ctx.print_color_centered(20, RGB::named(rltk::GREY), RGB::named(rltk::BLACK), &format!("Dungeon seed: {}", gs.seed));
 
-    ctx.print_color_centered(20, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Press any key to resume dungeon bashing!");
+    ctx.print_color_centered(22, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Press any key to resume dungeon bashing!");
 
-    match ctx.key {
-        None => {}
-        Some(_) => {
-            gs.game_state = TickType::PlayersTurn;
-        }
-    }
+    ctx.key.is_some()
 }
 
 #[allow(non_snake_case)]
-pub fn display_help_info(ctx : &mut Rltk, gs : &mut State) {
-    ctx.draw_box(10, 8, 60, 17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+pub fn display_help_info(ctx : &mut Rltk) -> bool {
+    ctx.draw_box(10, 8, 60, 22, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
     ctx.print_color_centered(10, RGB::named(rltk::WHITE), RGB::named(rltk::RED), "Controls");
     ctx.print_color_centered(12, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Arrow keys or NumPad keys to move.");
     ctx.print_color_centered(13, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Walk into a monster to attack it.");
-    ctx.print_color_centered(14, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "NumPad 5, or W to Wait.");
-    ctx.print_color_centered(15, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "G to Get an item from the ground.");
-    ctx.print_color_centered(16, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "U to Use an item from your inventory.");
-    ctx.print_color_centered(17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "E to Equip an item from your inventory.");
-    ctx.print_color_centered(17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "R to Remove an item you are using.");
-    ctx.print_color_centered(17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "D to Drop an item from your inventory.");
-    ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "> to go down stairs, if you are standing on them.");
-    ctx.print_color_centered(19, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "C for Character Info.");
-    ctx.print_color_centered(20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "? for this help menu. You've found this one.");
-    ctx.print_color_centered(21, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "ESCAPE to save the game and quit to the menu.");
-
-    ctx.print_color_centered(23, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Press any key to resume dungeon bashing!");
-
-    match ctx.key {
-        None => {}
-        Some(_) => {
-            gs.game_state = TickType::PlayersTurn;
-        }
-    }
+    ctx.print_color_centered(14, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Walk into a closed door to open it, K to close one.");
+    ctx.print_color_centered(15, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "S to Search for hidden traps around you.");
+    ctx.print_color_centered(16, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Z to Sneak, which is slower but quieter.");
+    ctx.print_color_centered(17, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "NumPad 5, or W to Wait.");
+    ctx.print_color_centered(18, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "G to Get an item from the ground.");
+    ctx.print_color_centered(19, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "U to Use an item from your inventory.");
+    ctx.print_color_centered(20, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "E to Equip an item from your inventory.");
+    ctx.print_color_centered(21, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "R to Remove an item you are using.");
+    ctx.print_color_centered(22, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "D to Drop an item from your inventory.");
+    ctx.print_color_centered(23, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "> or < to go down or up stairs you are standing on.");
+    ctx.print_color_centered(24, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "C for Character Info.");
+    ctx.print_color_centered(25, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "? for this help menu. You've found this one.");
+    ctx.print_color_centered(26, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "ESCAPE to save the game and quit to the menu.");
+
+    ctx.print_color_centered(28, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Press any key to resume dungeon bashing!");
+
+    ctx.key.is_some()
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+use crate::rltk;
+use rltk::{Rltk, VirtualKeyCode};
+use super::{gui, gui::ItemMenuResult, State, TickType, Command};
+
+// Front end: reads the keyboard/mouse for the current screen and turns it into a command.
+// Menus are drawn here too, since they need to know which entry the key picked.
+pub fn read_command(gs : &mut State, ctx : &mut Rltk) -> Option<Command> {
+    match gs.game_state {
+        TickType::PlayersTurn => { player_turn_command(ctx) }
+        TickType::UseMenu => { item_menu_command(gui::handle_item_menu(gs, ctx, "Use which item? (or ESC)"), Command::UseItem) }
+        TickType::DropMenu => { item_menu_command(gui::handle_item_menu(gs, ctx, "Drop which item? (or ESC)"), Command::DropItem) }
+        TickType::WieldMenu => { item_menu_command(gui::handle_equippable_menu(gs, ctx, "Wield which item? (or ESC)"), Command::EquipItem) }
+        TickType::UnequipMenu => { item_menu_command(gui::handle_equipped_menu(gs, ctx, "Unequip which item? (or ESC)"), Command::UnequipItem) }
+        TickType::TargetingItem => {
+            match gui::handle_item_targeting(gs, ctx, "Select your target with cursor keys or mouse, Escape to cancel.") {
+                ItemMenuResult::NoResponse => { None }
+                ItemMenuResult::Cancel => { Some(Command::Cancel) }
+                ItemMenuResult::Selected => { Some(Command::Target(gs.target_cell)) }
+            }
+        }
+        TickType::LevelUpMenu => { gui::handle_level_up(ctx, gs).map(Command::LevelUp) }
+        TickType::CharacterMenu => { if gui::display_character_info(ctx, gs) { Some(Command::Cancel) } else { None } }
+        TickType::HelpMenu => { if gui::display_help_info(ctx) { Some(Command::Cancel) } else { None } }
+        _ => { None }
+    }
+}
+
+fn item_menu_command(menu : (ItemMenuResult, i32), command : fn(i32) -> Command) -> Option<Command> {
+    match menu.0 {
+        ItemMenuResult::NoResponse => { None }
+        ItemMenuResult::Cancel => { Some(Command::Cancel) }
+        ItemMenuResult::Selected => { Some(command(menu.1)) }
+    }
+}
+
+fn player_turn_command(ctx : &mut Rltk) -> Option<Command> {
+    match ctx.key {
+        None => { None }
+        Some(key) => {
+            match key {
+                // Numpad
+                VirtualKeyCode::Numpad8 => { Some(Command::Move(0, -1)) }
+                VirtualKeyCode::Numpad4 => { Some(Command::Move(-1, 0)) }
+                VirtualKeyCode::Numpad6 => { Some(Command::Move(1, 0)) }
+                VirtualKeyCode::Numpad2 => { Some(Command::Move(0, 1)) }
+
+                VirtualKeyCode::Numpad7 => { Some(Command::Move(-1, -1)) }
+                VirtualKeyCode::Numpad9 => { Some(Command::Move(1, -1)) }
+                VirtualKeyCode::Numpad1 => { Some(Command::Move(-1, 1)) }
+                VirtualKeyCode::Numpad3 => { Some(Command::Move(1, 1)) }
+
+                // Cursors
+                VirtualKeyCode::Up => { Some(Command::Move(0, -1)) }
+                VirtualKeyCode::Down => { Some(Command::Move(0, 1)) }
+                VirtualKeyCode::Left => { Some(Command::Move(-1, 0)) }
+                VirtualKeyCode::Right => { Some(Command::Move(1, 0)) }
+
+                // Wait
+                VirtualKeyCode::Numpad5 => { Some(Command::Wait) }
+                VirtualKeyCode::W => { Some(Command::Wait) }
+
+                // Items
+                VirtualKeyCode::G => { Some(Command::PickUp) }
+                VirtualKeyCode::U => { Some(Command::ShowUseMenu) }
+                VirtualKeyCode::D => { Some(Command::ShowDropMenu) }
+                VirtualKeyCode::E => { Some(Command::ShowEquipMenu) }
+                VirtualKeyCode::R => { Some(Command::ShowUnequipMenu) }
+
+                // Doors
+                VirtualKeyCode::K => { Some(Command::CloseDoor) }
+
+                // Traps
+                VirtualKeyCode::S => { Some(Command::Search) }
+
+                // Stealth
+                VirtualKeyCode::Z => { Some(Command::ToggleSneak) }
+
+                // Level Change
+                VirtualKeyCode::Period => { Some(Command::Descend) }
+                VirtualKeyCode::Comma => { Some(Command::Ascend) }
+
+                // Character Info
+                VirtualKeyCode::C => { Some(Command::ShowCharacterInfo) }
+                VirtualKeyCode::Slash => { Some(Command::ShowHelp) }
+                _ => { None }
+            }
+        }
+    }
+}
@@ -1,147 +1,48 @@
-use crate::rltk;
-use rltk::{RGB, Point};
-use super::{BaseEntity, Map, random_choice};
 extern crate serde;
 use serde::{Serialize, Deserialize};
 
-#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
-pub enum ItemType { HealthPotion, ZapScroll, FireballScroll, ConfusionScroll, Sword, Shield }
+// What happens when an item is used. The numbers come from the raw files.
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum ItemType {
+    Healing,
+    Zap { damage : i32 },
+    Fireball { damage : i32, radius : i32 },
+    Confusion { turns : i32 },
+    Equipment,
+    // Opens the locked doors with the same lock number, and is used up doing it
+    Key { lock : i32 }
+}
 
-#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
-pub enum ItemSlot { MainHand, OffHand }
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum ItemSlot { MainHand, OffHand, Feet }
 
-#[derive(PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct Equippable {
     pub slot : ItemSlot,
     pub power_bonus : i32,
-    pub defense_bonus : i32
+    pub defense_bonus : i32,
+    #[serde(default)]
+    pub attack_cost : i32, // Extra energy an attack takes while this is equipped; heavy weapons are slower
+    #[serde(default)]
+    pub stealth_bonus : i32 // Quietens every noise the wearer makes by this many steps
 }
 
+// Anything that can be picked up. Lying on the floor it also has a Position;
+// in a backpack it is listed in the owner's Inventory instead.
 #[derive(PartialEq, Clone, Serialize, Deserialize)]
 pub struct Item {
-    pub position : Point,
-    pub glyph: u8,
-    pub fg : RGB,
-    pub name : String,
-    pub item_type : ItemType,
-    pub requires_targeting_mode : bool,
-    pub equippable : Option<Equippable>
+    pub item_type : ItemType
 }
 
 impl Item {
-    pub fn new_random(x:i32, y:i32) -> Item {
-        let choice = random_choice(vec![
-            ("Health".to_string(), 45), 
-            ("Zap".to_string(), 10), 
-            ("Fireball".to_string(), 10), 
-            ("Confusion".to_string(), 10),
-            ("Sword".to_string(), 10),
-            ("Shield".to_string(), 10),
-            ("Dagger".to_string(), 5),
-        ]);
-        
-        if choice == "Health" { Item::new_health_potion(x,y) }
-        else if choice == "Zap" { Item::new_zap_scroll(x,y) }
-        else if choice == "Fireball" { Item::new_fireball_scroll(x,y) }
-        else if choice == "Sword" { Item::new_sword(x,y) }
-        else if choice == "Shield" { Item::new_shield(x,y) }
-        else if choice == "Dagger" { Item::new_dagger(x,y) }
-        else { Item::new_confusion_scroll(x,y) }
-    }
-
-    pub fn new_health_potion(x:i32, y:i32) -> Item {
-        Item{ 
-            position: Point::new(x, y), 
-            glyph: 173, 
-            fg: RGB::named(rltk::MAGENTA), 
-            name: "Health Potion".to_string(),
-            item_type: ItemType::HealthPotion,
-            requires_targeting_mode : false,
-            equippable: None
-        }
-    }
-
-    pub fn new_zap_scroll(x:i32, y:i32) -> Item {
-        Item{ 
-            position: Point::new(x, y), 
-            glyph: 63, 
-            fg: RGB::named(rltk::CYAN), 
-            name: "Zap Scroll".to_string(),
-            item_type: ItemType::ZapScroll,
-            requires_targeting_mode : false,
-            equippable: None
-        }
-    }
-
-    pub fn new_fireball_scroll(x:i32, y:i32) -> Item {
-        Item{ 
-            position: Point::new(x, y), 
-            glyph: 63, 
-            fg: RGB::named(rltk::ORANGE), 
-            name: "Fireball Scroll".to_string(),
-            item_type: ItemType::FireballScroll,
-            requires_targeting_mode : true,
-            equippable: None
-        }
+    pub fn requires_targeting_mode(&self) -> bool {
+        matches!(self.item_type, ItemType::Fireball{..})
     }
 
-    pub fn new_confusion_scroll(x:i32, y:i32) -> Item {
-        Item{ 
-            position: Point::new(x, y), 
-            glyph: 63, 
-            fg: RGB::named(rltk::BLUE), 
-            name: "Confusion Scroll".to_string(),
-            item_type: ItemType::ConfusionScroll,
-            requires_targeting_mode : false,
-            equippable: None
-        }
-    }
-
-    pub fn new_sword(x:i32, y:i32) -> Item {
-        Item{ 
-            position: Point::new(x, y), 
-            glyph: 47, 
-            fg: RGB::named(rltk::CYAN), 
-            name: "Sword".to_string(),
-            item_type: ItemType::Sword,
-            requires_targeting_mode : false,
-            equippable: Some(Equippable{ slot : ItemSlot::MainHand, power_bonus: 1, defense_bonus: 0 })
-        }
-    }
-
-    pub fn new_dagger(x:i32, y:i32) -> Item {
-        Item{ 
-            position: Point::new(x, y), 
-            glyph: 47, 
-            fg: RGB::named(rltk::GREEN), 
-            name: "Dagger".to_string(),
-            item_type: ItemType::Sword,
-            requires_targeting_mode : false,
-            equippable: Some(Equippable{ slot : ItemSlot::MainHand, power_bonus: 2, defense_bonus: 0 })
-        }
-    }
-
-    pub fn new_shield(x:i32, y:i32) -> Item {
-        Item{ 
-            position: Point::new(x, y), 
-            glyph: 93, 
-            fg: RGB::named(rltk::BROWN1), 
-            name: "Shield".to_string(),
-            item_type: ItemType::Shield,
-            requires_targeting_mode : false,
-            equippable: Some(Equippable{ slot : ItemSlot::OffHand, power_bonus: 0, defense_bonus: 1 })
-        }
+    // Whether a mob that seeks loot will go after it. Keys are left where they are,
+    // so the player can always find them.
+    pub fn is_loot(&self) -> bool {
+        !matches!(self.item_type, ItemType::Key{..})
     }
 }
-
-#[typetag::serde(name = "BEItem")]
-impl BaseEntity for Item {
-    fn get_position(&self) -> Point { self.position }
-    fn get_fg_color(&self) -> RGB { self.fg }
-    fn get_glyph(&self) -> u8 { self.glyph }
-    fn plot_visibility(&mut self, _map : &Map) {}
-    fn get_tooltip_text(&self) -> String { format!("Item: {}", self.name) }
-    fn get_name(&self) -> String { self.name.clone() }
-    fn can_pickup(&self) -> bool { true }
-    fn as_item(&self) -> Option<&Item> { Some(self) }
-}
\ No newline at end of file
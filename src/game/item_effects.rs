@@ -1,123 +1,131 @@
-use super::{State, BaseEntity, TickType, Combat, Particle};
+use super::{State, Particle, EntityId, Confusion, systems, player, scheduler, noise};
 use crate::rltk;
 use rltk::{RGB};
 
 pub fn use_health_potion(item_index : i32, gs : &mut State, result : &mut Vec<String>) {
-    let player = &mut gs.player_mut();
-    if player.fighter.hp == player.fighter.max_hp {
+    let fighter = gs.player_fighter_mut();
+    if fighter.hp == fighter.max_hp {
         result.push("You are already at maximum health.".to_string());
     } else {
-        player.fighter.hp = player.fighter.max_hp; // Cheezed due to confusion over borrowing
+        fighter.hp = fighter.max_hp;
         result.push("You are healed!".to_string());
-        player.inventory.remove_item_return_clone(item_index);
+        consume_item(item_index, gs);
     }
 }
 
-pub fn use_zap_scroll(item_index : i32, gs : &mut State, result : &mut Vec<String>) {
-    let mut possible_targets : Vec<(usize, f32)> = Vec::new();
-    let visible_tiles = gs.player().visible_tiles.clone();
-    let my_pos = gs.player().get_position();
-    for (i,potential_target) in gs.entities.iter().enumerate() {
-        if potential_target.is_mob() {
-            let target_pos = potential_target.get_position();
-            if visible_tiles.contains(&target_pos) {
-                possible_targets.push((i, rltk::DistanceAlg::Pythagoras.distance2d(my_pos, target_pos)));
-            }
-        }
-    }
+pub fn use_zap_scroll(item_index : i32, damage : i32, gs : &mut State, result : &mut Vec<String>) {
+    let possible_targets = visible_mobs_by_distance(gs);
+    let my_pos = gs.player_position();
 
     if possible_targets.is_empty() {
         result.push("You can't see anyone to zap, so you put the scroll away.".to_string());
     } else {
-        possible_targets.sort_by(|a,b| a.1.partial_cmp(&b.1).unwrap());
+        let target = possible_targets[0];
+        let target_name = gs.world.name(target);
 
-        let target = &mut gs.entities[possible_targets[0].0].as_mob_mut().unwrap();
-
-        let tp = target.get_position();
+        let tp = *gs.world.positions.get(target).unwrap();
         let line = rltk::line2d(rltk::LineAlg::Bresenham, tp, my_pos);
         for zap in line {
             gs.vfx.push(Particle::new(zap, RGB::named(rltk::CYAN), RGB::named(rltk::BLACK), 15, 200.0));
         }
 
-        result.push(format!("Lightning from the scroll zaps {} for 8 points of damage.", target.name));
-        target.take_damage(8);
-        if target.fighter.hp < 1 { 
-            target.kill();
-            result.push(format!("{} is burned to a crisp.", target.name));
-            gs.player_mut().xp += target.fighter.xp_value;
+        result.push(format!("Lightning from the scroll zaps {} for {} points of damage.", target_name, damage));
+        let mut xp = 0;
+        if let Some(fighter) = gs.world.fighters.get_mut(target) {
+            fighter.take_damage(damage);
+            if fighter.hp < 1 {
+                fighter.kill();
+                result.push(format!("{} is burned to a crisp.", target_name));
+                xp = fighter.xp_value;
+            }
         }
-        gs.entities.retain(|e| !e.is_dead());
+        gs.player_mut().xp += xp;
+        systems::delete_the_dead(gs);
 
         // Remove the scroll
-        gs.player_mut().inventory.remove_item_return_clone(item_index);
+        consume_item(item_index, gs);
+        noise::player_noise(gs, noise::SCROLL_NOISE);
     }
 }
 
-pub fn use_fireball_scroll(gs : &mut State, result : &mut Vec<String>) {
+pub fn use_fireball_scroll(damage : i32, radius : i32, gs : &mut State, result : &mut Vec<String>) {
     result.push("You launch a fireball!".to_string());
 
     let target = gs.target_cell;
     let item_index = gs.targeting_item;
 
-    let area_of_effect = rltk::field_of_view(target, 3, &gs.map);
+    let area_of_effect = rltk::field_of_view(target, radius, &gs.map);
     for pos in area_of_effect.iter() {
         gs.vfx.push(Particle::new(*pos, RGB::named(rltk::RED), RGB::named(rltk::YELLOW), 176, 200.0));
     }
-    let mut targets : Vec<usize> = Vec::new();
-    for (i,e) in gs.entities.iter().enumerate() {
-        if area_of_effect.contains(&e.get_position()) && e.can_be_attacked() { targets.push(i); }
+    let mut targets : Vec<EntityId> = Vec::new();
+    for (id,_fighter) in gs.world.fighters.iter() {
+        if let Some(pos) = gs.world.positions.get(id) {
+            if area_of_effect.contains(pos) { targets.push(id); }
+        }
     }
 
     for target_id in targets {
-        let target = gs.entities[target_id].as_combat();
-        match target {
-            None => {}
-            Some(target) => {
-                result.push(format!("{} is burned for 8 points of damage.", target.get_name()));
-                target.take_damage(8);
-                if target.get_hp() < 1 { 
-                    result.push(format!("{} is dead.", target.get_name()));
-                    target.kill();
-                    gs.player_mut().xp += target.xp_value();
-                }
+        let target_name = gs.world.name(target_id);
+        let mut xp = 0;
+        if let Some(fighter) = gs.world.fighters.get_mut(target_id) {
+            result.push(format!("{} is burned for {} points of damage.", target_name, damage));
+            fighter.take_damage(damage);
+            if fighter.hp < 1 {
+                result.push(format!("{} is dead.", target_name));
+                fighter.kill();
+                xp = fighter.xp_value;
             }
         }
+        gs.player_mut().xp += xp;
     }
 
-    gs.entities.retain(|e| !(e.is_dead() && !e.is_player()));
+    systems::delete_the_dead(gs);
 
     // Remove the scroll
-    gs.player_mut().inventory.remove_item_return_clone(item_index);
-    gs.game_state = TickType::EnemyTurn;
+    consume_item(item_index, gs);
+    noise::player_noise(gs, noise::SCROLL_NOISE);
+    player::end_turn(gs, scheduler::ACTION_COST);
 
     for r in result {
         gs.add_log_entry(r.to_string());
     }
 }
 
-pub fn use_confusion_scroll(item_index : i32, gs : &mut State, result : &mut Vec<String>) {
-    let mut possible_targets : Vec<(usize, f32)> = Vec::new();
-    let visible_tiles = gs.player().visible_tiles.clone();
-    let my_pos = gs.player().get_position();
-    for (i,potential_target) in gs.entities.iter().enumerate() {
-        if potential_target.is_mob() {
-            let target_pos = potential_target.get_position();
-            if visible_tiles.contains(&target_pos) {
-                possible_targets.push((i, rltk::DistanceAlg::Pythagoras.distance2d(my_pos, target_pos)));
-            }
-        }
-    }
+pub fn use_confusion_scroll(item_index : i32, turns : i32, gs : &mut State, result : &mut Vec<String>) {
+    let possible_targets = visible_mobs_by_distance(gs);
 
     if possible_targets.is_empty() {
         result.push("You can't see anyone to zap, so you put the scroll away.".to_string());
     } else {
-        possible_targets.sort_by(|a,b| a.1.partial_cmp(&b.1).unwrap());
-
-        let mut target = &mut gs.entities[possible_targets[0].0].as_mob_mut().unwrap();
-        result.push(format!("{} is confused.", target.name));
-        target.confused = Some(5);
+        let target = possible_targets[0];
+        result.push(format!("{} is confused.", gs.world.name(target)));
+        gs.world.confusion.insert(target, Confusion{ turns });
 
         // Remove the scroll
-        gs.player_mut().inventory.remove_item_return_clone(item_index);
+        consume_item(item_index, gs);
+        noise::player_noise(gs, noise::SCROLL_NOISE);
     }
-}
\ No newline at end of file
+}
+
+// Used-up items leave the backpack and the world
+fn consume_item(item_index : i32, gs : &mut State) {
+    let item = gs.player_inventory_mut().remove_item(item_index);
+    gs.world.delete_entity(item);
+}
+
+// Mobs the player can see, nearest first
+fn visible_mobs_by_distance(gs : &State) -> Vec<EntityId> {
+    let mut possible_targets : Vec<(EntityId, f32)> = Vec::new();
+    let visible_tiles = &gs.player_viewshed().visible_tiles;
+    let my_pos = gs.player_position();
+    for (id,_mob) in gs.world.mobs.iter() {
+        if let Some(target_pos) = gs.world.positions.get(id) {
+            if visible_tiles.contains(target_pos) {
+                possible_targets.push((id, rltk::DistanceAlg::Pythagoras.distance2d(my_pos, *target_pos)));
+            }
+        }
+    }
+    possible_targets.sort_by(|a,b| a.1.partial_cmp(&b.1).unwrap());
+    possible_targets.iter().map(|t| t.0).collect()
+}
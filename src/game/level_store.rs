@@ -0,0 +1,37 @@
+use std::collections::BTreeMap;
+use super::{Map, EntityBundle};
+extern crate serde;
+use serde::{Serialize, Deserialize};
+
+// A level the player isn't on: its map (with what has been revealed) and
+// everything that was left on it.
+#[derive(Serialize, Deserialize)]
+pub struct StoredLevel {
+    pub map : Map,
+    pub entities : Vec<EntityBundle>
+}
+
+// Every visited level other than the current one, by depth. Saved with the game.
+#[derive(Serialize, Deserialize)]
+pub struct LevelStore {
+    levels : BTreeMap<i32, StoredLevel>
+}
+
+impl LevelStore {
+    pub fn new() -> LevelStore {
+        LevelStore{ levels: BTreeMap::new() }
+    }
+
+    pub fn store(&mut self, depth : i32, level : StoredLevel) {
+        self.levels.insert(depth, level);
+    }
+
+    // Removes the level from the store; it becomes the live one
+    pub fn take(&mut self, depth : i32) -> Option<StoredLevel> {
+        self.levels.remove(&depth)
+    }
+}
+
+impl Default for LevelStore {
+    fn default() -> LevelStore { LevelStore::new() }
+}
@@ -0,0 +1,77 @@
+use crate::rltk;
+use rltk::{RGB, Point, Algorithm2D, field_of_view};
+use super::{State, Map, LightSource, GameRng};
+use rand::Rng;
+
+// Below this much light a tile is too dark to make out, unless it's right next to you
+pub const SEE_THRESHOLD : f32 = 0.25;
+// Out of 100, from depth 1 on: a level with no light of its own
+const DARK_LEVEL_CHANCE : i32 = 20;
+const SURFACE_AMBIENT : f32 = 0.8;
+const AMBIENT_LOSS_PER_DEPTH : f32 = 0.06;
+const MIN_AMBIENT : f32 = 0.3;
+
+// How much light a level has everywhere, before any torches. It gets gloomier
+// further down, and now and then a level is pitch black.
+pub fn ambient_light(depth : i32, rng : &mut GameRng) -> f32 {
+    if depth > 0 && rng.gen_range(0, 100) < DARK_LEVEL_CHANCE { return 0.0; }
+    f32::max(MIN_AMBIENT, SURFACE_AMBIENT - (AMBIENT_LOSS_PER_DEPTH * depth as f32))
+}
+
+pub fn is_dark_level(map : &Map) -> bool {
+    map.ambient_light < SEE_THRESHOLD
+}
+
+// Works out the light on every tile: the level's ambient light, plus each light
+// source's colour, fading out towards the edge of its range
+pub fn lighting_system(gs : &mut State) {
+    let ambient = gs.map.ambient_light;
+    let mut light = vec![(ambient, ambient, ambient); gs.map.tiles.len()];
+
+    for (pos, source) in light_sources(gs) {
+        for tile in field_of_view(pos, source.range, &gs.map) {
+            if tile.x < 0 || tile.y < 0 || tile.x >= gs.map.width || tile.y >= gs.map.height { continue; }
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(pos, tile);
+            let intensity = f32::max(0.0, 1.0 - (distance / (source.range as f32 + 1.0)));
+            let lit = &mut light[gs.map.point2d_to_index(tile) as usize];
+            lit.0 += source.colour.r * intensity;
+            lit.1 += source.colour.g * intensity;
+            lit.2 += source.colour.b * intensity;
+        }
+    }
+
+    gs.map.light = light.iter().map(|(r, g, b)| RGB::from_f32(f32::min(1.0, *r), f32::min(1.0, *g), f32::min(1.0, *b))).collect();
+}
+
+// Lights lying around, and lights being carried (which shine from whoever carries them)
+fn light_sources(gs : &State) -> Vec<(Point, LightSource)> {
+    let mut sources = Vec::new();
+    for (id, source) in gs.world.lights.iter() {
+        if let Some(pos) = gs.world.positions.get(id) { sources.push((*pos, source.clone())); }
+    }
+    for (owner, inventory) in gs.world.inventories.iter() {
+        if let Some(pos) = gs.world.positions.get(owner) {
+            for item in inventory.items.iter().chain(inventory.equipped.iter()) {
+                if let Some(source) = gs.world.lights.get(*item) { sources.push((*pos, source.clone())); }
+            }
+        }
+    }
+    sources
+}
+
+// How brightly lit a tile is, whatever the colour of the light
+pub fn light_level(map : &Map, pos : Point) -> f32 {
+    match map.light.get(map.point2d_to_index(pos) as usize) {
+        None => 0.0,
+        Some(light) => f32::max(light.r, f32::max(light.g, light.b))
+    }
+}
+
+// What can be seen from pos: the field of view, less the tiles too dark to make out.
+// Whatever is right next to you can always be felt, if not seen.
+pub fn visible_tiles(map : &Map, pos : Point, range : i32) -> Vec<Point> {
+    field_of_view(pos, range, map).into_iter()
+        .filter(|tile| tile.x >= 0 && tile.y >= 0 && tile.x < map.width && tile.y < map.height)
+        .filter(|tile| light_level(map, *tile) >= SEE_THRESHOLD || rltk::DistanceAlg::Pythagoras.distance2d(pos, *tile) < 1.5)
+        .collect()
+}
@@ -1,17 +1,26 @@
 use crate::rltk;
-use rltk::{ Point, Algorithm2D, BaseMap };
+use rltk::{ Point, RGB, Algorithm2D, BaseMap };
 use super::TileType;
 extern crate serde;
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize)]
+// New levels are bigger than the screen; the camera scrolls around them
+pub const MAP_WIDTH : i32 = 120;
+pub const MAP_HEIGHT : i32 = 80;
+const FULL_LIGHT : f32 = 1.0;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(into = "SavedMap", from = "SavedMap")]
 pub struct Map {
     pub tiles : Vec<TileType>,
     pub visible : Vec<bool>,
     pub revealed : Vec<bool>,
     pub width: i32,
     pub height: i32,
-    pub blocked : Vec<bool>
+    pub blocked : Vec<bool>,
+    // Light on each tile, worked out again every turn by the lighting system
+    pub light : Vec<RGB>,
+    pub ambient_light : f32
 }
 
 impl Map {
@@ -27,7 +36,8 @@ impl Map {
             blocked.push(false);
         }
 
-        Map{tiles : blank_map, visible, revealed, width: w, height: h, blocked }
+        let light = vec![RGB::from_f32(1.0, 1.0, 1.0); (w*h) as usize];
+        Map{tiles : blank_map, visible, revealed, width: w, height: h, blocked, light, ambient_light: FULL_LIGHT }
     }
 
     pub fn set_visibility(&mut self, vis : &[Point]) {
@@ -42,7 +52,7 @@ impl Map {
     }    
 
     // Utility function: find the index of a tile at x/y
-    fn tile_idx(&self, x:i32, y:i32) -> Option<usize> {
+    pub fn tile_idx(&self, x:i32, y:i32) -> Option<usize> {
         if self.valid_tile(x, y) {
             Some(((y*self.width)+x) as usize)
         } else {
@@ -64,6 +74,10 @@ impl Map {
                     TileType::Floor => { true }
                     TileType::Wall => { false }
                     TileType::Stairs => { true }
+                    TileType::UpStairs => { true }
+                    TileType::ClosedDoor => { true }
+                    TileType::OpenDoor => { true }
+                    TileType::LockedDoor(_) => { false }
                 }
             }
 
@@ -82,6 +96,10 @@ impl Map {
                     TileType::Floor => { false }
                     TileType::Wall => { true }
                     TileType::Stairs => { false }
+                    TileType::UpStairs => { false }
+                    TileType::ClosedDoor => { true }
+                    TileType::OpenDoor => { false }
+                    TileType::LockedDoor(_) => { true }
                 }
             }
 
@@ -108,7 +126,11 @@ impl Map {
                     match self.tiles[x] {
                         TileType::Floor => { return "Floor".to_string() }
                         TileType::Wall => { return "Wall".to_string() }
-                        TileType::Stairs => { return "Stairs".to_string() }
+                        TileType::Stairs => { return "Stairs down".to_string() }
+                        TileType::UpStairs => { return "Stairs up".to_string() }
+                        TileType::ClosedDoor => { return "Closed door".to_string() }
+                        TileType::OpenDoor => { return "Open door".to_string() }
+                        TileType::LockedDoor(_) => { return "Locked door".to_string() }
                     }
                 }
             }
@@ -116,6 +138,11 @@ impl Map {
         "".to_string()
     }
 
+    // Where the first tile of a type is, if there is one (e.g. the stairs)
+    pub fn find_tile(&self, tile : TileType) -> Option<Point> {
+        self.tiles.iter().position(|t| *t == tile).map(|idx| self.index_to_point2d(idx as i32))
+    }
+
     pub fn refresh_blocked(&mut self) {
         for y in 0..self.height {
             for x in 0..self.width {
@@ -144,6 +171,40 @@ impl Map {
     }    
 }
 
+// What a map looks like in a save: visible, blocked and light are worked out again after
+// loading, and revealed is packed into a string of 0s and 1s.
+#[derive(Serialize, Deserialize)]
+struct SavedMap {
+    tiles : Vec<TileType>,
+    revealed : String,
+    width : i32,
+    height : i32,
+    #[serde(default = "full_light")]
+    ambient_light : f32
+}
+
+fn full_light() -> f32 { FULL_LIGHT }
+
+impl From<Map> for SavedMap {
+    fn from(map : Map) -> SavedMap {
+        let revealed = map.revealed.iter().map(|r| if *r { '1' } else { '0' }).collect();
+        SavedMap{ tiles: map.tiles, revealed, width: map.width, height: map.height, ambient_light: map.ambient_light }
+    }
+}
+
+impl From<SavedMap> for Map {
+    fn from(saved : SavedMap) -> Map {
+        let size = (saved.width * saved.height) as usize;
+        let mut revealed : Vec<bool> = saved.revealed.chars().map(|c| c == '1').collect();
+        revealed.resize(size, false);
+        let mut tiles = saved.tiles;
+        tiles.resize(size, TileType::Wall);
+        let ambient = saved.ambient_light;
+        Map{ tiles, visible: vec![false; size], revealed, width: saved.width, height: saved.height, blocked: vec![false; size],
+             light: vec![RGB::from_f32(ambient, ambient, ambient); size], ambient_light: ambient }
+    }
+}
+
 impl BaseMap for Map {
     fn is_opaque(&self, idx: i32) -> bool {
         self.is_transparent(idx % self.width, idx / self.width)
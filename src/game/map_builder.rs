@@ -1,133 +1,290 @@
-use super::{ Map, Rect, TileType, Mob, Item };
+use super::{ Map, Rect, TileType, World, AiState, GameRng, spawner, prefab, map_validation, treasure_room, treasure_room::TreasureRoom, trap, lighting, random, SimpleMapBuilder, BspMapBuilder, CaveMapBuilder, MAP_WIDTH, MAP_HEIGHT };
+use crate::rltk;
+use rltk::Point;
 use rand::Rng;
 use std::cmp::{max, min};
+use std::collections::BTreeMap;
 
-const ROOM_MAX_SIZE : i32 = 10;
-const ROOM_MIN_SIZE : i32 = 6;
-const MAX_ROOMS : i32 = 30;
+// Levels that fail validation are built again in the same style, up to this many
+// times, before falling back to the plain rooms builder.
+const MAX_BUILD_ATTEMPTS : u32 = 10;
+// If even the plain rooms builder can't make a playable level in this many tries
+// all told, something is wrong (e.g. a vault that never fits); settle for plain rooms
+// with nothing added rather than lose the player's game
+const MAX_TOTAL_ATTEMPTS : u32 = MAX_BUILD_ATTEMPTS * 3;
+const BUILDER_STYLES : usize = 3;
+// Out of 100; the rest of the doors start closed
+const OPEN_DOOR_CHANCE : i32 = 20;
+// Traps are kept at least this far from where the player arrives
+const TRAP_CLEARANCE : f32 = 5.0;
+// One wall torch per this many tiles of map; dark levels get a third as many
+const TILES_PER_TORCH : i32 = 400;
+// Out of 100; the rest of the monsters start out wandering
+const ASLEEP_CHANCE : i32 = 40;
 
-pub fn random_rooms_tut3(map : &mut Map) -> Vec<Rect> {
-    let mut rng = rand::thread_rng();
+// How a builder has fared since the game started
+#[derive(Clone, Copy, Default)]
+pub struct BuildStats {
+    pub levels : u32,
+    pub retries : u32,
+    pub repairs : u32,
+    // Levels where nothing would build, so plain rooms were used instead
+    pub fallbacks : u32
+}
 
-    let mut rooms : Vec<Rect> = Vec::new();
-    for _i in 1..MAX_ROOMS {
-        let w = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE);
-        let h = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE);
-        let x = rng.gen_range(1, map.width - w - 1);
-        let y = rng.gen_range(1, map.height - h - 1);
+// The stats for every builder, kept by whoever is building the levels
+#[derive(Default)]
+pub struct BuildLog {
+    pub stats : BTreeMap<&'static str, BuildStats>
+}
 
-        let room_candidate = Rect::new(x, y, x+w, y+h);
+impl BuildLog {
+    // One line per builder: levels made, how many needed rebuilding or repairing, and
+    // how many were plain rooms because nothing else would build
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (name, stats) in self.stats.iter() {
+            let tries = stats.levels + stats.retries;
+            let retry_percent = (stats.retries * 100).checked_div(tries).unwrap_or(0);
+            report += &format!("{}: {} levels, {} retries ({}% of attempts), {} repaired, {} fallbacks\n", name, stats.levels, stats.retries, retry_percent, stats.repairs, stats.fallbacks);
+        }
+        report
+    }
+}
+
+// A level generator. Each one builds a map in its own style, and reports where the
+// player starts, where the stairs down are, and where monsters and items may go.
+pub trait MapBuilder {
+    fn name(&self) -> &'static str;
+    fn build_map(&mut self, rng : &mut GameRng);
+    fn map(&self) -> Map;
+    fn starting_position(&self) -> Point;
+    fn stairs_position(&self) -> Point;
+    // Places to spawn things; these never include where the player starts
+    fn spawn_regions(&self) -> Vec<SpawnRegion>;
+    // Rooms that could be locked up as a treasure room, if the style has any
+    fn rooms(&self) -> Vec<Rect> { Vec::new() }
+}
 
-        let mut collides = false;
-        for room in rooms.iter() {
-            if room_candidate.intersect(room) {
-                collides = true;
+// A patch of floor that gets its own handful of monsters and items, e.g. a room
+#[derive(Clone)]
+pub struct SpawnRegion {
+    pub tiles : Vec<Point>
+}
+
+impl SpawnRegion {
+    // The inside of a room, leaving a gap by the walls
+    pub fn from_rect(rect : &Rect) -> SpawnRegion {
+        let mut tiles = Vec::new();
+        for y in rect.y1 + 1 .. rect.y2 - 1 {
+            for x in rect.x1 + 1 .. rect.x2 - 1 {
+                tiles.push(Point::new(x, y));
             }
         }
+        SpawnRegion{ tiles }
+    }
+}
+
+// A finished level: what the builder made, with a vault added if one fitted
+pub struct BuiltLevel {
+    pub map : Map,
+    pub start : Point,
+    pub spawn_regions : Vec<SpawnRegion>,
+    // Mobs and items placed by the vault, by their names in the raws
+    pub fixed_spawns : Vec<(Point, String)>,
+    pub treasure : Option<TreasureRoom>
+}
 
-        if !collides {
-            apply_room(map, &room_candidate);
-
-            if !rooms.is_empty() {
-                let (new_x, new_y) = room_candidate.center();
-                let (prev_x, prev_y) = rooms[rooms.len()-1].center();
-                if rng.gen_range(0,1)==1 {
-                    apply_horizontal_tunnel(map, prev_x, new_x, prev_y);
-                    apply_vertical_tunnel(map, prev_y, new_y, new_x);
-                } else {
-                    apply_vertical_tunnel(map, prev_y, new_y, prev_x);
-                    apply_horizontal_tunnel(map, prev_x, new_x, new_y);
-                }
+// Builds a level, checks that it can be played, and tries again if it can't
+pub fn build_level(width : i32, height : i32, depth : i32, rng : &mut GameRng, log : &mut BuildLog) -> BuiltLevel {
+    let mut style = rng.gen_range(0, BUILDER_STYLES);
+    let mut attempts = 0;
+    loop {
+        let mut builder = new_builder(style, width, height);
+        builder.build_map(rng);
+        let mut map = builder.map();
+        let start = builder.starting_position();
+        let fixed_spawns = prefab::place_vault(&mut map, start, depth, rng);
+        let mut spawn_regions = builder.spawn_regions();
+        let treasure = treasure_room::seal_treasure_room(&mut map, &builder.rooms(), start, &mut spawn_regions, depth, rng);
+        let mut level = BuiltLevel{ map, start, spawn_regions, fixed_spawns, treasure };
+
+        let result = map_validation::validate_level(&mut level);
+        let stats = log.stats.entry(builder.name()).or_default();
+        match result {
+            Ok(repaired) => {
+                stats.levels += 1;
+                if repaired { stats.repairs += 1; }
+                return level;
+            }
+            Err(_) => {
+                stats.retries += 1;
+                attempts += 1;
+                if attempts >= MAX_TOTAL_ATTEMPTS { return plain_level(width, height, rng, log); }
+                if attempts >= MAX_BUILD_ATTEMPTS { style = 0; }
             }
+        }
+    }
+}
+
+// Plain rooms with no vault or treasure room. The rooms builder joins every room
+// up, so this can be played even if it doesn't pass validation.
+fn plain_level(width : i32, height : i32, rng : &mut GameRng, log : &mut BuildLog) -> BuiltLevel {
+    let mut builder = SimpleMapBuilder::new(width, height);
+    builder.build_map(rng);
+    let mut level = BuiltLevel{ map: builder.map(), start: builder.starting_position(), spawn_regions: builder.spawn_regions(), fixed_spawns: Vec::new(), treasure: None };
+    let stats = log.stats.entry(builder.name()).or_default();
+    stats.levels += 1;
+    stats.fallbacks += 1;
+    if let Ok(true) = map_validation::validate_level(&mut level) { stats.repairs += 1; }
+    level
+}
+
+fn new_builder(style : usize, width : i32, height : i32) -> Box<dyn MapBuilder> {
+    match style {
+        1 => Box::new(BspMapBuilder::new(width, height)),
+        2 => Box::new(CaveMapBuilder::new(width, height)),
+        _ => Box::new(SimpleMapBuilder::new(width, height))
+    }
+}
 
-            rooms.push(room_candidate);
+// Builds a batch of levels at every depth from 0 to 9, for checking on the builders
+pub fn mapgen_report(levels_per_depth : u32) -> String {
+    let mut rng = random::new_rng(random::random_seed());
+    let mut log = BuildLog::default();
+    for depth in 0..10 {
+        for _i in 0..levels_per_depth {
+            build_level(MAP_WIDTH, MAP_HEIGHT, depth, &mut rng, &mut log);
         }
     }
-    rooms
+    log.report()
 }
 
 // Applies a rectangle room to the map
-fn apply_room(map : &mut Map, rect : &Rect) {
+pub fn apply_room(map : &mut Map, rect : &Rect) {
     for y in min(rect.y1, rect.y2) .. max(rect.y1, rect.y2) {
         for x in min(rect.x1, rect.x2) .. max(rect.x1, rect.x2) {
-            let idx = (y * map.width) + x;
-            if idx > 0 && idx < map.width*map.height {
-                map.tiles[idx as usize] = TileType::Floor;
-            }
+            set_floor(map, x, y);
         }
     }
 }
 
-fn apply_horizontal_tunnel(map: &mut Map, x1:i32, x2:i32, y:i32) {
+pub fn apply_horizontal_tunnel(map: &mut Map, x1:i32, x2:i32, y:i32) {
     for x in min(x1,x2) ..= max(x1,x2) {
-        let idx = (y * map.width) + x;
-        if idx > 0 && idx < map.width*map.height {
-            map.tiles[idx as usize] = TileType::Floor;
-        }
+        set_floor(map, x, y);
     }
 }
 
-fn apply_vertical_tunnel(map: &mut Map, y1:i32, y2:i32, x:i32) {
+pub fn apply_vertical_tunnel(map: &mut Map, y1:i32, y2:i32, x:i32) {
     for y in min(y1,y2) ..= max(y1,y2) {
-        let idx = (y * map.width) + x;
-        if idx > 0 && idx < map.width*map.height {
-            map.tiles[idx as usize] = TileType::Floor;
+        set_floor(map, x, y);
+    }
+}
+
+// Puts doors where corridors break through the walls around rooms: a floor tile just
+// outside a room, with wall on either side of it, that isn't next to another door.
+pub fn place_doors(map : &mut Map, rooms : &[Rect], rng : &mut GameRng) {
+    for room in rooms.iter() {
+        let mut candidates = Vec::new();
+        for y in room.y1 .. room.y2 {
+            candidates.push(Point::new(room.x1 - 1, y));
+            candidates.push(Point::new(room.x2, y));
+        }
+        for x in room.x1 .. room.x2 {
+            candidates.push(Point::new(x, room.y1 - 1));
+            candidates.push(Point::new(x, room.y2));
+        }
+
+        for pos in candidates {
+            if is_door_spot(map, pos) {
+                let idx = ((pos.y * map.width) + pos.x) as usize;
+                map.tiles[idx] = if rng.gen_range(0, 100) < OPEN_DOOR_CHANCE { TileType::OpenDoor } else { TileType::ClosedDoor };
+            }
         }
     }
 }
 
-pub fn spawn_mobs(rooms: &[Rect], dungeon_level : i32) -> Vec<Mob> {
-    let mut rng = rand::thread_rng();
-    let mut mobs : Vec<Mob> = Vec::new();
-    for i in 1 .. rooms.len() {
+fn is_door_spot(map : &Map, pos : Point) -> bool {
+    if pos.x < 1 || pos.y < 1 || pos.x > map.width - 2 || pos.y > map.height - 2 { return false; }
+    let tile = |dx : i32, dy : i32| map.tiles[(((pos.y + dy) * map.width) + pos.x + dx) as usize];
+    if tile(0, 0) != TileType::Floor { return false; }
+
+    let walled_sideways = tile(-1, 0) == TileType::Wall && tile(1, 0) == TileType::Wall;
+    let walled_above_below = tile(0, -1) == TileType::Wall && tile(0, 1) == TileType::Wall;
+    let next_to_door = [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+        .any(|(dx, dy)| tile(*dx, *dy) == TileType::ClosedDoor || tile(*dx, *dy) == TileType::OpenDoor);
+    (walled_sideways || walled_above_below) && !next_to_door
+}
+
+// Digs out a tile, leaving the map's outer wall alone
+fn set_floor(map : &mut Map, x : i32, y : i32) {
+    if x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1 {
+        map.tiles[((y * map.width) + x) as usize] = TileType::Floor;
+    }
+}
+
+pub fn spawn_mobs(world : &mut World, regions: &[SpawnRegion], dungeon_level : i32, rng : &mut GameRng) {
+    for region in regions.iter().filter(|r| !r.tiles.is_empty()) {
         let number_of_mobs = rng.gen_range(1, dungeon_level+3);
-        if number_of_mobs > 0 {
-            for _mobn in 1 .. number_of_mobs {
-                let mob_x = rng.gen_range(rooms[i].x1+1, rooms[i].x2-1);
-                let mob_y = rng.gen_range(rooms[i].y1+1, rooms[i].y2-1);
-
-                let mut found = false;
-                for existing_mob in mobs.iter() {
-                    if existing_mob.position.x == mob_x && existing_mob.position.y == mob_y {
-                        found = true;
-                    }
-                }
-
-                if !found {
-                    let mob = Mob::new_random(mob_x, mob_y);
-                    mobs.push(mob);
-                }
+        for _mobn in 1 .. number_of_mobs {
+            let pos = region.tiles[rng.gen_range(0, region.tiles.len())];
+            if has_mob_at(world, pos) { continue; }
+            if let Some(id) = spawner::random_mob(world, pos.x, pos.y, dungeon_level, rng) {
+                if rng.gen_range(0, 100) < ASLEEP_CHANCE { world.mobs.get_mut(id).unwrap().state = AiState::Asleep; }
             }
         }
     }
-    mobs
 }
 
-pub fn spawn_items(rooms: &[Rect], mobs: &[Mob], dungeon_level : i32) -> Vec<Item> {
-    let mut rng = rand::thread_rng();
-    let mut items : Vec<Item> = Vec::new();
-
-    for i in 1 .. rooms.len() {
+pub fn spawn_items(world : &mut World, regions: &[SpawnRegion], dungeon_level : i32, rng : &mut GameRng) {
+    for region in regions.iter().filter(|r| !r.tiles.is_empty()) {
         let number_of_items = rng.gen_range(1, dungeon_level+3);
-        if number_of_items > 0 {
-            for _itemn in 1 .. number_of_items {
-                let item_x = rng.gen_range(rooms[i].x1+1, rooms[i].x2-1);
-                let item_y = rng.gen_range(rooms[i].y1+1, rooms[i].y2-1);
-
-                let mut found = false;
-                for existing_mob in mobs.iter() {
-                    if existing_mob.position.x == item_x && existing_mob.position.y == item_y {
-                        found = true;
-                    }
-                }
-
-                if !found {
-                    let item = Item::new_random(item_x, item_y);
-                    items.push(item);
-                }
+        for _itemn in 1 .. number_of_items {
+            let pos = region.tiles[rng.gen_range(0, region.tiles.len())];
+            if !has_mob_at(world, pos) {
+                spawner::random_item(world, pos.x, pos.y, dungeon_level, rng);
             }
         }
     }
+}
+
+// Hides a few traps on open floor, in rooms and corridors alike
+pub fn spawn_traps(world : &mut World, map : &Map, start : Point, dungeon_level : i32, rng : &mut GameRng) {
+    let spots : Vec<Point> = (0 .. map.width * map.height)
+        .filter(|idx| map.tiles[*idx as usize] == TileType::Floor)
+        .map(|idx| Point::new(idx % map.width, idx / map.width))
+        .filter(|pos| rltk::DistanceAlg::Pythagoras.distance2d(*pos, start) > TRAP_CLEARANCE)
+        .collect();
+    if spots.is_empty() { return; }
+
+    let types = trap::trap_types(dungeon_level);
+    let number_of_traps = rng.gen_range(2, 5) + dungeon_level / 2;
+    for _trapn in 0 .. number_of_traps {
+        let pos = spots[rng.gen_range(0, spots.len())];
+        if world.traps.iter().any(|(id, _trap)| world.positions.get(id) == Some(&pos)) { continue; }
+        let trap_type = types[rng.gen_range(0, types.len())];
+        spawner::trap(world, pos.x, pos.y, trap_type);
+    }
+}
+
+// Puts torches on floor tiles against a wall
+pub fn spawn_torches(world : &mut World, map : &Map, rng : &mut GameRng) {
+    let spots : Vec<Point> = (0 .. map.width * map.height)
+        .filter(|idx| map.tiles[*idx as usize] == TileType::Floor)
+        .map(|idx| Point::new(idx % map.width, idx / map.width))
+        .filter(|pos| [(-1, 0), (1, 0), (0, -1), (0, 1)].iter().any(|(dx, dy)| map.tiles[(((pos.y + dy) * map.width) + pos.x + dx) as usize] == TileType::Wall))
+        .collect();
+    if spots.is_empty() { return; }
+
+    let mut number_of_torches = (map.width * map.height) / TILES_PER_TORCH;
+    if lighting::is_dark_level(map) { number_of_torches /= 3; }
+    for _torchn in 0 .. number_of_torches {
+        let pos = spots[rng.gen_range(0, spots.len())];
+        if world.lights.iter().any(|(id, _light)| world.positions.get(id) == Some(&pos)) { continue; }
+        spawner::torch(world, pos.x, pos.y);
+    }
+}
 
-    items
-}
\ No newline at end of file
+fn has_mob_at(world : &World, pos : Point) -> bool {
+    world.mobs.iter().any(|(id, _mob)| world.positions.get(id) == Some(&pos))
+}
@@ -0,0 +1,52 @@
+use super::{TileType, DijkstraMap, dijkstra::UNREACHABLE, map_builder::BuiltLevel};
+
+// Less reachable floor than this isn't worth playing (e.g. a cave start sealed in a pocket)
+const MIN_REACHABLE_TILES : usize = 250;
+
+// Checks a freshly built level from the player's start. Floor that can't be reached
+// is walled up and unreachable spawn points are dropped; that counts as a repair
+// (Ok(true)). If the stairs or anything the vault placed can't be reached, the key is
+// behind its own lock, or there's hardly anywhere to go, the level is no good and has
+// to be built again.
+pub fn validate_level(level : &mut BuiltLevel) -> Result<bool, String> {
+    let map = &mut level.map;
+    map.refresh_blocked();
+    let without_key = DijkstraMap::new(map, &[level.start]);
+
+    // Everything but the key only has to be reachable once the locked doors are open
+    for (idx, tile) in map.tiles.iter().enumerate() {
+        if let TileType::LockedDoor(_) = tile { map.blocked[idx] = false; }
+    }
+    let reachable = DijkstraMap::new(map, &[level.start]);
+
+    let stairs = match map.find_tile(TileType::Stairs) {
+        None => { return Err("there are no stairs down".to_string()) }
+        Some(stairs) => stairs
+    };
+    if reachable.distance(stairs) == UNREACHABLE { return Err("the stairs down can't be reached".to_string()); }
+    if let Some((pos, name)) = level.fixed_spawns.iter().find(|(pos, _name)| reachable.distance(*pos) == UNREACHABLE) {
+        return Err(format!("the {} at {},{} can't be reached", name, pos.x, pos.y));
+    }
+    if let Some(treasure) = &level.treasure {
+        if reachable.distance(treasure.key) == UNREACHABLE { return Err("the key can't be reached".to_string()); }
+        if without_key.distance(treasure.key) == UNREACHABLE { return Err("the key is behind its own lock".to_string()); }
+    }
+
+    let reachable_tiles = reachable.distances.iter().filter(|d| **d != UNREACHABLE).count();
+    if reachable_tiles < MIN_REACHABLE_TILES { return Err(format!("only {} tiles can be reached", reachable_tiles)); }
+
+    let mut repaired = false;
+    for (idx, tile) in map.tiles.iter_mut().enumerate() {
+        if *tile != TileType::Wall && reachable.distances[idx] == UNREACHABLE {
+            *tile = TileType::Wall;
+            repaired = true;
+        }
+    }
+    for region in level.spawn_regions.iter_mut() {
+        let before = region.tiles.len();
+        region.tiles.retain(|pos| reachable.distance(*pos) != UNREACHABLE);
+        if region.tiles.len() != before { repaired = true; }
+    }
+    level.spawn_regions.retain(|region| !region.tiles.is_empty());
+    Ok(repaired)
+}
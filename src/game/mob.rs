@@ -1,166 +1,398 @@
 use crate::rltk;
-use rltk::{RGB, Point, Algorithm2D, a_star_search, field_of_view};
-use super::{fighter::Fighter, Map, Combat, BaseEntity, State, attack, random_choice, Particle};
+use rltk::{RGB, Point, Algorithm2D, a_star_search};
+use super::{State, attack, attack_with, Particle, EntityId, TileType, Inventory, MobAi, PlayerMemory, AiState, scheduler, trap, lighting, noise, ai_maps::Goal, dijkstra::UNREACHABLE};
 use rand::Rng;
-extern crate serde;
-use serde::{Serialize, Deserialize};
-
-#[derive(Serialize, Deserialize)]
-pub struct Mob {
-    pub position : Point,
-    pub glyph: u8,
-    pub fg : RGB,
-    pub visible_tiles : Vec<Point>,
-    pub name : String,
-    pub fighter : Fighter,
-    pub confused: Option<i32>
-}
-
-impl Mob {
-    pub fn new_random(x:i32, y:i32) -> Mob {
-        let choice = random_choice(vec![("Wight".to_string(), 10), ("Hound".to_string(), 45), ("Itereater".to_string(), 45)]);
-        if choice == "Wight" { Mob::new_wight(x, y) }
-        else if choice == "Hound" { Mob::new_hound(x, y) }
-        else { Mob::new_iter(x, y) }
-    }
-
-    fn new_wight(x:i32, y:i32) -> Mob {
-        Mob{ 
-            position: Point::new(x, y), 
-            glyph: 38, 
-            fg: RGB::named(rltk::RED), 
-            visible_tiles: Vec::new(), 
-            name: "Borrow Wight".to_string(),
-            fighter: Fighter::new(2, 0, 1, 60),
-            confused: None
+
+// Loot further away than this (in steps) isn't worth the walk
+const LOOT_RANGE : f32 = 12.0;
+const MOB_CAPACITY : i32 = 5;
+// A mob that has lost sight of the player looks around within this many tiles of
+// where it last saw them, for this many turns, and gives up the chase altogether
+// after MEMORY_TURNS
+const SEARCH_RADIUS : f32 = 3.0;
+const SEARCH_TURNS : i32 = 10;
+const MEMORY_TURNS : i32 = 40;
+// Wandering mobs head for open ground at least this far away
+const MIN_WANDER_DISTANCE : f32 = 10.0;
+const WANDER_TRIES : i32 = 10;
+
+// Monster AI: called by the scheduler whenever a mob has the energy to act.
+// Every path through here spends energy, or the scheduler would never move on.
+pub fn mob_act(gs : &mut State, id : EntityId) {
+    let player_pos = gs.player_position();
+    let my_pos = match gs.world.positions.get(id) {
+        None => { scheduler::spend_energy(&mut gs.world, id, scheduler::ACTION_COST); return }
+        Some(p) => *p
+    };
+
+    if let Some(confusion) = gs.world.confusion.get_mut(id) {
+        confusion.turns -= 1;
+        if confusion.turns < 1 {
+            gs.world.confusion.remove(id);
         }
-    }
 
-    fn new_hound(x:i32, y:i32) -> Mob {
-        Mob{ 
-            position: Point::new(x, y), 
-            glyph: 109, 
-            fg: RGB::named(rltk::RED), 
-            visible_tiles: Vec::new(), 
-            name: "Mut Hound".to_string(),
-            fighter: Fighter::new(1, 0, 1, 30),
-            confused: None
+        let delta_x = gs.rng.gen_range(0, 3)-1;
+        let delta_y = gs.rng.gen_range(0, 3)-1;
+        let new_loc = Point::new(my_pos.x + delta_x, my_pos.y + delta_y);
+        let new_idx = gs.map.point2d_to_index(new_loc);
+        if gs.map.is_walkable(new_loc.x, new_loc.y) && !gs.map.is_tile_blocked(new_idx) && gs.map.tiles[new_idx as usize] != TileType::ClosedDoor {
+            move_mob(gs, id, my_pos, new_loc);
         }
+
+        scheduler::spend_energy(&mut gs.world, id, scheduler::ACTION_COST);
+        return;
     }
 
-    fn new_iter(x:i32, y:i32) -> Mob {
-        Mob{ 
-            position: Point::new(x, y), 
-            glyph: 105, 
-            fg: RGB::named(rltk::RED), 
-            visible_tiles: Vec::new(), 
-            name: "Itereater Beast".to_string(),
-            fighter: Fighter::new(1, 0, 1, 30),
-            confused: None
+    let can_see_player = match gs.world.viewsheds.get(id) {
+        None => false,
+        Some(viewshed) => viewshed.visible_tiles.contains(&player_pos)
+    };
+
+    if gs.world.mobs.get(id).is_some_and(|mob| mob.state == AiState::Asleep) {
+        if wakes_up(gs, id, can_see_player) {
+            set_state(gs, id, AiState::default());
+            if gs.map.is_tile_visible(my_pos) {
+                let line = format!("{} wakes up.", gs.world.name(id));
+                gs.add_log_entry(line);
+            }
         }
+        scheduler::spend_energy(&mut gs.world, id, scheduler::ACTION_COST);
+        return;
     }
 
-    pub fn turn_tick(&mut self, player_pos : Point, map : &mut Map) -> bool {
-        if let Some(turns) = self.confused {
-            let new_turns = turns-1;
-            if new_turns == 0 {
-                self.confused = None;
-            } else {
-                self.confused = Some(new_turns);
+    let ai = gs.world.mobs.get(id).map(|mob| mob.ai).unwrap_or_default();
+    if can_see_player {
+        if let Some(mob) = gs.world.mobs.get_mut(id) {
+            mob.alerted_to = None;
+            mob.memory = Some(PlayerMemory::new(player_pos));
+        }
+        let distance = rltk::DistanceAlg::Pythagoras.distance2d(player_pos, my_pos);
+        let frightened = is_frightened(gs, id, &ai);
+        set_state(gs, id, if frightened { AiState::Fleeing } else { AiState::Hunting });
+        let backing_off = frightened || distance < ai.keep_distance as f32;
+        let in_range = gs.world.ranged.get(id).is_some_and(|ranged| distance <= ranged.range as f32);
+        // Anything cornered next to the player fights
+        if !(backing_off && step_towards(gs, id, my_pos, Goal::AwayFromPlayer)) {
+            if distance < 1.5 {
+                attack_player(gs, id);
+                return;
             }
-
-            let mut rng = rand::thread_rng();
-            let delta_x = rng.gen_range(0, 3)-1;
-            let delta_y = rng.gen_range(0, 3)-1;
-            let new_loc = Point::new(self.position.x + delta_x, self.position.y + delta_y);
-            if map.is_walkable(new_loc.x, new_loc.y) && !map.is_tile_blocked(map.point2d_to_index(new_loc)) {
-                self.position = new_loc;
+            if in_range && has_line_of_fire(gs, my_pos, player_pos) {
+                shoot_player(gs, id, my_pos);
+                return;
+            }
+            // A shooter with something in the way moves in for a clear shot
+            if !backing_off && (distance > ai.keep_distance as f32 || in_range) {
+                step_towards(gs, id, my_pos, Goal::Player);
             }
+        }
+    } else if remembers_player(gs, id, &ai) {
+        set_state(gs, id, AiState::Hunting);
+        hunt(gs, id, my_pos);
+    } else if let Some(target) = gs.world.mobs.get(id).and_then(|mob| mob.alerted_to) {
+        // Go and see what the fuss was about, and give up once there
+        set_state(gs, id, AiState::Hunting);
+        if rltk::DistanceAlg::Pythagoras.distance2d(target, my_pos) < 1.5 || !path_to(gs, id, my_pos, target) {
+            if let Some(mob) = gs.world.mobs.get_mut(id) { mob.alerted_to = None; }
+        }
+    } else if ai.seeks_loot && has_room_for_loot(gs, id) && pick_up_loot(gs, id, my_pos) {
+        set_state(gs, id, AiState::default());
+    } else {
+        let near_loot = ai.seeks_loot && has_room_for_loot(gs, id) && gs.ai_maps.as_ref().is_some_and(|maps| maps.to_items.distance(my_pos) <= LOOT_RANGE);
+        if !(near_loot && step_towards(gs, id, my_pos, Goal::Loot)) { wander(gs, id, my_pos); }
+    }
+    scheduler::spend_energy(&mut gs.world, id, scheduler::ACTION_COST);
+}
+
+fn set_state(gs : &mut State, id : EntityId, state : AiState) {
+    if let Some(mob) = gs.world.mobs.get_mut(id) {
+        // Wandering on from wandering keeps the path it was following
+        if !(matches!(mob.state, AiState::Wandering{..}) && matches!(state, AiState::Wandering{..})) { mob.state = state; }
+    }
+}
+
+// A sleeping mob wakes when it hears something or is hurt, or when it sees the
+// player and the player fails to sneak past
+fn wakes_up(gs : &mut State, id : EntityId, can_see_player : bool) -> bool {
+    let alerted = gs.world.mobs.get(id).is_some_and(|mob| mob.alerted_to.is_some());
+    let hurt = gs.world.fighters.get(id).is_some_and(|fighter| fighter.hp < fighter.max_hp);
+    alerted || hurt || (can_see_player && gs.rng.gen_range(0, 100) >= noise::unnoticed_chance(gs))
+}
 
-            return false;
+// Follows the mob's path to somewhere else on the level, picking a new one when it
+// gets there. Anything in the way (another mob, or a door shut since the path was
+// planned) means planning again next turn.
+fn wander(gs : &mut State, id : EntityId, my_pos : Point) {
+    let mut path = match gs.world.mobs.get(id).map(|mob| &mob.state) {
+        Some(AiState::Wandering{ path }) => path.clone(),
+        _ => Vec::new()
+    };
+    if path.is_empty() { path = wander_path(gs, id, my_pos); }
+
+    if let Some(next) = path.first().cloned() {
+        let idx = gs.map.point2d_to_index(next);
+        let opens_doors = gs.world.mobs.get(id).is_some_and(|mob| mob.opens_doors);
+        if gs.map.tiles[idx as usize] == TileType::ClosedDoor {
+            if opens_doors { open_door(gs, id, idx); } else { path.clear(); }
+        } else if !gs.map.is_walkable(next.x, next.y) || gs.map.is_tile_blocked(idx) {
+            path.clear();
+        } else {
+            path.remove(0);
+            move_mob(gs, id, my_pos, next);
         }
+    }
+    if let Some(mob) = gs.world.mobs.get_mut(id) { mob.state = AiState::Wandering{ path }; }
+}
 
-        let can_see_player = self.visible_tiles.contains(&player_pos);
+// A path to somewhere in the open (most likely another room), or nowhere if none turns up
+fn wander_path(gs : &mut State, id : EntityId, my_pos : Point) -> Vec<Point> {
+    for _try in 0 .. WANDER_TRIES {
+        let target = Point::new(gs.rng.gen_range(1, gs.map.width - 1), gs.rng.gen_range(1, gs.map.height - 1));
+        if rltk::DistanceAlg::Pythagoras.distance2d(my_pos, target) < MIN_WANDER_DISTANCE || !is_open_ground(gs, target) { continue; }
+        if let Some(path) = find_path(gs, id, my_pos, target) { return path; }
+    }
+    Vec::new()
+}
 
-        if can_see_player {
-            let distance = rltk::DistanceAlg::Pythagoras.distance2d(player_pos, self.position);
-            if distance < 1.5 {
-                return true;
-            } else {
-                self.path_to_player(player_pos, map);
-            }
+// Floor with floor all around it, which rules out corridors and doorways
+fn is_open_ground(gs : &State, pos : Point) -> bool {
+    if gs.map.is_tile_blocked(gs.map.point2d_to_index(pos)) { return false; }
+    (-1 ..= 1).all(|dy| (-1 ..= 1).all(|dx| gs.map.tiles[gs.map.point2d_to_index(Point::new(pos.x + dx, pos.y + dy)) as usize] == TileType::Floor))
+}
+
+// Whether the mob still has the player on its mind; a frightened one would rather forget
+fn remembers_player(gs : &mut State, id : EntityId, ai : &MobAi) -> bool {
+    let frightened = is_frightened(gs, id, ai);
+    let mob = match gs.world.mobs.get_mut(id) {
+        None => { return false }
+        Some(mob) => mob
+    };
+    if let Some(memory) = &mut mob.memory {
+        memory.turns_ago += 1;
+        if frightened || memory.turns_ago > MEMORY_TURNS || memory.turns_searched > SEARCH_TURNS { mob.memory = None; }
+    }
+    mob.memory.is_some()
+}
+
+// Heads for where the player was last seen, then looks around there
+fn hunt(gs : &mut State, id : EntityId, my_pos : Point) {
+    let last_seen = match gs.world.mobs.get(id).and_then(|mob| mob.memory) {
+        None => { return }
+        Some(memory) => memory.last_seen
+    };
+    if rltk::DistanceAlg::Pythagoras.distance2d(last_seen, my_pos) <= SEARCH_RADIUS {
+        if let Some(memory) = gs.world.mobs.get_mut(id).and_then(|mob| mob.memory.as_mut()) { memory.turns_searched += 1; }
+        search_around(gs, id, my_pos, last_seen);
+    } else if !path_to(gs, id, my_pos, last_seen) {
+        if let Some(mob) = gs.world.mobs.get_mut(id) { mob.memory = None; }
+    }
+}
+
+// A random step that stays near the spot being searched
+fn search_around(gs : &mut State, id : EntityId, my_pos : Point, spot : Point) {
+    let opens_doors = gs.world.mobs.get(id).is_some_and(|mob| mob.opens_doors);
+    let mut steps = Vec::new();
+    for dy in -1 ..= 1 {
+        for dx in -1 ..= 1 {
+            let pos = Point::new(my_pos.x + dx, my_pos.y + dy);
+            if pos == my_pos || !gs.map.is_walkable(pos.x, pos.y) { continue; }
+            let idx = gs.map.point2d_to_index(pos);
+            if gs.map.is_tile_blocked(idx) || rltk::DistanceAlg::Pythagoras.distance2d(spot, pos) > SEARCH_RADIUS { continue; }
+            if !opens_doors && gs.map.tiles[idx as usize] == TileType::ClosedDoor { continue; }
+            if trap::known_trap_at(gs, id, pos) { continue; }
+            steps.push(pos);
         }
-        false
-    }
-
-    fn path_to_player(&mut self, player_pos : Point, map : &mut Map) {
-        let path = a_star_search(map.point2d_to_index(self.position), map.point2d_to_index(player_pos), map);
-        if path.success {
-            let idx = path.steps[1];
-            if !map.is_tile_blocked(idx) {
-                let old_idx = (self.position.y * map.width) + self.position.x;
-                map.clear_tile_blocked(old_idx);
-                map.set_tile_blocked(idx);
-                self.position = map.index_to_point2d(idx);
+    }
+    if steps.is_empty() { return; }
+    let pos = steps[gs.rng.gen_range(0, steps.len())];
+    let idx = gs.map.point2d_to_index(pos);
+    if gs.map.tiles[idx as usize] == TileType::ClosedDoor {
+        open_door(gs, id, idx);
+    } else {
+        move_mob(gs, id, my_pos, pos);
+    }
+}
+
+fn is_frightened(gs : &State, id : EntityId, ai : &MobAi) -> bool {
+    match gs.world.fighters.get(id) {
+        None => false,
+        Some(fighter) => fighter.hp * 100 < fighter.max_hp * ai.flee_below
+    }
+}
+
+// Takes one step downhill on the shared map for goal; returns false if there is
+// no step that gets any closer
+fn step_towards(gs : &mut State, id : EntityId, my_pos : Point, goal : Goal) -> bool {
+    let destination = match &gs.ai_maps {
+        None => None,
+        Some(maps) => best_step(gs, id, my_pos, maps.get(goal))
+    };
+    match destination {
+        None => false,
+        Some(pos) => {
+            let idx = gs.map.point2d_to_index(pos);
+            if gs.map.tiles[idx as usize] == TileType::ClosedDoor {
+                open_door(gs, id, idx);
+            } else {
+                move_mob(gs, id, my_pos, pos);
             }
+            true
         }
     }
 }
 
-#[typetag::serde(name = "BEMob")]
-impl BaseEntity for Mob {
-    fn get_position(&self) -> Point { self.position }
-    fn get_fg_color(&self) -> RGB { self.fg }
-    fn get_glyph(&self) -> u8 { self.glyph }
-    fn as_combat(&mut self) -> Option<&mut Combat> { Some(self) }
-    fn plot_visibility(&mut self, map : &Map) {
-        self.visible_tiles = field_of_view(self.get_position(), 6, map);
-    }
-    fn get_tooltip_text(&self) -> String { format!("Enemy: {}", self.name) }
-    fn blocks_tile(&self) -> bool { true }
-    fn can_be_attacked(&self) -> bool { true }
-    fn is_dead(&self) -> bool { self.fighter.dead }
-    fn is_mob(&self) -> bool { true }
-    fn as_mob_mut(&mut self) ->Option<&mut Mob> { Some(self) }
-    fn get_name(&self) -> String { self.name.to_string() }
-}
-
-pub fn mob_tick(gs : &mut State) {
-    // Build the master map of unavailable tiles
-    gs.map.refresh_blocked();
-    for e in gs.entities.iter() {
-        if e.blocks_tile() {
-            let pos = e.get_position();
-            gs.map.set_tile_blocked(gs.map.point2d_to_index(pos));
+// The neighbouring tile lowest on the map that the mob can get into. Closed doors
+// are walls to mobs that can't open them, and traps the mob knows of are only
+// stepped on when there's no other way forward.
+fn best_step(gs : &State, id : EntityId, my_pos : Point, dijkstra : &super::DijkstraMap) -> Option<Point> {
+    let opens_doors = gs.world.mobs.get(id).is_some_and(|mob| mob.opens_doors);
+    let player_pos = gs.player_position();
+    let here = dijkstra.distance(my_pos);
+    let mut best : Option<(Point, f32)> = None;
+    let mut best_trap : Option<(Point, f32)> = None;
+
+    for dy in -1 ..= 1 {
+        for dx in -1 ..= 1 {
+            let pos = Point::new(my_pos.x + dx, my_pos.y + dy);
+            if pos == my_pos || pos == player_pos || !gs.map.is_walkable(pos.x, pos.y) { continue; }
+            let idx = gs.map.point2d_to_index(pos);
+            if gs.map.is_tile_blocked(idx) { continue; }
+            if !opens_doors && gs.map.tiles[idx as usize] == TileType::ClosedDoor { continue; }
+            let distance = dijkstra.distance(pos);
+            if distance == UNREACHABLE || distance >= here { continue; }
+
+            let has_trap = trap::known_trap_at(gs, id, pos);
+            let slot = if has_trap { &mut best_trap } else { &mut best };
+            if slot.is_none_or(|(_pos, lowest)| distance < lowest) { *slot = Some((pos, distance)); }
         }
     }
+    best.or(best_trap).map(|(pos, _distance)| pos)
+}
+
+fn has_room_for_loot(gs : &State, id : EntityId) -> bool {
+    gs.world.inventories.get(id).is_none_or(|inventory| !inventory.is_full())
+}
+
+// Picks up an item from the floor under the mob, if there is one worth taking
+fn pick_up_loot(gs : &mut State, id : EntityId, my_pos : Point) -> bool {
+    let item_id = match gs.world.items.iter().find(|(item_id, item)| item.is_loot() && gs.world.positions.get(*item_id) == Some(&my_pos)) {
+        None => { return false }
+        Some((item_id, _item)) => item_id
+    };
+    if !gs.world.inventories.contains(id) { gs.world.inventories.insert(id, Inventory::new(MOB_CAPACITY)); }
+    gs.world.inventories.get_mut(id).unwrap().items.push(item_id);
+    gs.world.positions.remove(item_id);
+
+    if gs.map.is_tile_visible(my_pos) {
+        let line = format!("{} picks up the {}.", gs.world.name(id), gs.world.name(item_id));
+        gs.add_log_entry(line);
+    }
+    true
+}
+
+fn attack_player(gs : &mut State, id : EntityId) {
+    let ppos = gs.player_position();
+    gs.vfx.push(Particle::new(ppos, RGB::named(rltk::RED), RGB::named(rltk::BLACK), 176, 200.0));
+    let cost = scheduler::attack_cost(&gs.world, id);
+    let (_xp, result) = attack(&mut gs.world, id, gs.player_id);
+    for r in result {
+        gs.add_log_entry(r);
+    }
+    noise::make_noise(gs, ppos, noise::COMBAT_NOISE);
+    scheduler::spend_energy(&mut gs.world, id, cost);
+}
+
+// Nothing between the two points that would stop a shot: no wall or closed door,
+// and nobody standing in the way
+fn has_line_of_fire(gs : &State, from : Point, to : Point) -> bool {
+    rltk::line2d(rltk::LineAlg::Bresenham, from, to).iter()
+        .filter(|pos| **pos != from && **pos != to)
+        .all(|pos| !gs.map.is_transparent(pos.x, pos.y) && !gs.map.is_tile_blocked(gs.map.point2d_to_index(*pos)))
+}
+
+fn shoot_player(gs : &mut State, id : EntityId, my_pos : Point) {
+    let ranged = match gs.world.ranged.get(id) {
+        None => { return }
+        Some(ranged) => ranged.clone()
+    };
+    let ppos = gs.player_position();
+    for pos in rltk::line2d(rltk::LineAlg::Bresenham, my_pos, ppos).iter().filter(|pos| **pos != my_pos) {
+        gs.vfx.push(Particle::new(*pos, ranged.colour, RGB::named(rltk::BLACK), ranged.glyph, 200.0));
+    }
+    let cost = scheduler::attack_cost(&gs.world, id);
+    let (_xp, result) = attack_with(&mut gs.world, id, gs.player_id, &ranged.verb);
+    for r in result {
+        gs.add_log_entry(r);
+    }
+    noise::make_noise(gs, ppos, noise::COMBAT_NOISE);
+    scheduler::spend_energy(&mut gs.world, id, cost);
+}
+
+// Takes one step along the path to target; returns false if there is no way there
+fn path_to(gs : &mut State, id : EntityId, my_pos : Point, target : Point) -> bool {
+    let next = match find_path(gs, id, my_pos, target) {
+        None => { return false }
+        Some(path) => path[0]
+    };
+    let idx = gs.map.point2d_to_index(next);
+    if gs.map.tiles[idx as usize] == TileType::ClosedDoor {
+        open_door(gs, id, idx);
+    } else if !gs.map.is_tile_blocked(idx) {
+        move_mob(gs, id, my_pos, next);
+    }
+    true
+}
 
-    let mut active_mobs : Vec<usize> = Vec::new();
-    for (i,e) in gs.entities.iter_mut().enumerate() {
-        if e.is_mob() { active_mobs.push(i); }
+// The steps from my_pos to target, not counting where the mob is now
+fn find_path(gs : &mut State, id : EntityId, my_pos : Point, target : Point) -> Option<Vec<Point>> {
+    // Mobs that can't open doors path around closed ones, as if they were walls
+    let opens_doors = gs.world.mobs.get(id).is_some_and(|mob| mob.opens_doors);
+    let closed_doors : Vec<i32> = if opens_doors { Vec::new() } else {
+        gs.map.tiles.iter().enumerate().filter(|(_idx, tile)| **tile == TileType::ClosedDoor).map(|(idx, _tile)| idx as i32).collect()
+    };
+    // Known traps are walked around too, unless there's no other way
+    let traps : Vec<i32> = gs.world.traps.iter()
+        .filter_map(|(trap_id, _trap)| gs.world.positions.get(trap_id))
+        .filter(|pos| **pos != target && trap::known_trap_at(gs, id, **pos))
+        .map(|pos| gs.map.point2d_to_index(*pos))
+        .filter(|idx| !gs.map.is_tile_blocked(*idx))
+        .collect();
+
+    for idx in closed_doors.iter() { gs.map.set_tile_blocked(*idx); }
+    for idx in traps.iter() { gs.map.set_tile_blocked(*idx); }
+    let mut path = a_star_search(gs.map.point2d_to_index(my_pos), gs.map.point2d_to_index(target), &mut gs.map);
+    for idx in traps.iter() { gs.map.clear_tile_blocked(*idx); }
+    if !path.success && !traps.is_empty() {
+        path = a_star_search(gs.map.point2d_to_index(my_pos), gs.map.point2d_to_index(target), &mut gs.map);
     }
+    for idx in closed_doors.iter() { gs.map.clear_tile_blocked(*idx); }
 
-    let ppos = gs.player().position;
-    let mut attacking_mobs : Vec<usize> = Vec::new();
+    if !path.success || path.steps.len() < 2 { return None; }
+    Some(path.steps[1..].iter().map(|idx| gs.map.index_to_point2d(*idx)).collect())
+}
 
-    for id in active_mobs {
-        let mob = gs.entities[id].as_mob_mut().unwrap();
-        if mob.turn_tick(ppos, &mut gs.map) {
-            attacking_mobs.push(id);
-        }
+// Opening a door takes the mob's move; the door stays open behind it
+fn open_door(gs : &mut State, id : EntityId, idx : i32) {
+    gs.map.tiles[idx as usize] = TileType::OpenDoor;
+    if gs.map.visible[idx as usize] {
+        let line = format!("{} opens a door.", gs.world.name(id));
+        gs.add_log_entry(line);
     }
+    gs.update_visibility();
+}
 
-    let mut tmp : Vec<String> = Vec::new();
-    for id in attacking_mobs {
-        let attacker_name = gs.entities[id].get_name();
-        let attacker_power = gs.entities[id].as_combat().unwrap().get_power();
-        gs.vfx.push(Particle::new(gs.player().get_position(), RGB::named(rltk::RED), RGB::named(rltk::BLACK), 176, 200.0));
-        let (_xp, result) = attack(attacker_name, attacker_power, gs.player_as_combat());
-        for r in result {
-            tmp.push(r);
+fn move_mob(gs : &mut State, id : EntityId, from : Point, to : Point) {
+    let old_idx = gs.map.point2d_to_index(from);
+    let new_idx = gs.map.point2d_to_index(to);
+    gs.map.clear_tile_blocked(old_idx);
+    gs.map.set_tile_blocked(new_idx);
+    gs.world.positions.insert(id, to);
+    trap::trigger_trap_at(gs, id, to);
+
+    // A fast mob may act again before the player does, so it needs to see from where it is now
+    if let Some(pos) = gs.world.positions.get(id) {
+        if let Some(viewshed) = gs.world.viewsheds.get_mut(id) {
+            viewshed.visible_tiles = lighting::visible_tiles(&gs.map, *pos, viewshed.range);
         }
     }
-    for s in tmp {
-        gs.add_log_entry(s);
-    }
 }
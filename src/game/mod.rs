@@ -4,8 +4,22 @@ use rltk::{ GameState };
 
 extern crate serde;
 
-mod entity;
-pub use entity::BaseEntity;
+mod ecs;
+pub use ecs::{EntityId, EntityBundle, Storage, World};
+
+mod components;
+pub use components::{Renderable, Viewshed, Name, Confusion, Mob, MobAi, PlayerMemory, AiState, Player, BlocksTile, Energy, LightSource, RangedAttack};
+
+mod spawner;
+
+mod raws;
+pub use raws::{load_raws, RawError, RAW_FILE};
+
+mod systems;
+
+mod lighting;
+
+mod scheduler;
 
 mod tiletype;
 pub use tiletype::TileType;
@@ -15,41 +29,87 @@ pub use ticktype::TickType;
 
 mod fighter;
 pub use fighter::Fighter;
-pub use fighter::Combat;
-pub use fighter::attack;
+pub use fighter::{attack, attack_with};
 
 mod player;
-pub use player::Player;
 
 mod mob;
-pub use mob::Mob;
 
 mod rect;
 pub use rect::Rect;
 
 mod map;
-pub use map::Map;
+pub use map::{Map, MAP_WIDTH, MAP_HEIGHT};
+
+mod camera;
+pub use camera::{Camera, SCREEN_WIDTH, SCREEN_HEIGHT};
+
+mod level_store;
+pub use level_store::{LevelStore, StoredLevel};
 
 mod item;
-use item::Item;
-use item::ItemType;
+pub use item::{Item, ItemType, ItemSlot, Equippable};
 
 mod inventory;
-use inventory::Inventory;
+pub use inventory::Inventory;
 
 mod item_effects;
 
+mod trap;
+pub use trap::{Trap, TrapType};
+
 extern crate rand;
+extern crate rand_pcg;
 
 mod map_builder;
+pub use map_builder::{MapBuilder, SpawnRegion, BuiltLevel, BuildStats, BuildLog, build_level, mapgen_report};
+
+mod map_validation;
+pub use map_validation::validate_level;
+
+mod treasure_room;
+
+mod simple_map;
+pub use simple_map::SimpleMapBuilder;
+
+mod bsp_map;
+pub use bsp_map::BspMapBuilder;
+
+mod cave_map;
+pub use cave_map::CaveMapBuilder;
+
+mod dijkstra;
+pub use dijkstra::DijkstraMap;
+
+mod ai_maps;
+pub use ai_maps::AiMaps;
+
+mod noise;
+
+mod prefab;
+pub use prefab::{load_prefabs, parse_prefab, Prefab, PREFAB_DIR};
 
 mod gui;
 
 mod gamestate;
 pub use gamestate::State;
 
+mod command;
+pub use command::{Command, LevelUpChoice};
+
+mod input;
+
+mod session;
+pub use session::GameSession;
+
+mod savegame;
+pub use savegame::{save_game, load_game, parse_save, delete_save, list_slots, SlotInfo, SlotSummary, SaveError, SAVE_VERSION, SAVE_SLOTS};
+
+mod replay;
+pub use replay::{Replay, ReplayRecorder};
+
 mod random;
-pub use random::random_choice;
+pub use random::{random_choice, random_seed, GameRng};
 
 mod vfx;
 pub use vfx::Particle;
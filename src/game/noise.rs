@@ -0,0 +1,86 @@
+use crate::rltk;
+use rltk::{Point, Algorithm2D};
+use super::{State, TileType};
+use std::cmp::{min, Reverse};
+use std::collections::BinaryHeap;
+
+// How far (in steps) each kind of noise carries
+pub const WALK_NOISE : i32 = 4;
+pub const SNEAK_NOISE : i32 = 1;
+pub const DOOR_NOISE : i32 = 6;
+pub const SCROLL_NOISE : i32 = 8;
+pub const COMBAT_NOISE : i32 = 10;
+pub const ALARM_NOISE : i32 = 25;
+// Sound going through a door travels this many extra steps' worth
+const DOOR_MUFFLING : i32 = 4;
+// Out of 100: the player's chance, each turn, of going unnoticed by a sleeping mob
+// that can see them, and how much each point of stealth and sneaking adds to it
+const BASE_UNNOTICED : i32 = 75;
+const UNNOTICED_PER_STEALTH : i32 = 5;
+const SNEAKING_UNNOTICED : i32 = 10;
+const MAX_UNNOTICED : i32 = 95;
+
+// Every mob that hears a noise made at pos comes to see what it was, waking up if
+// it was asleep. Sound goes round walls rather than through them, so it carries
+// as far as loudness steps along the ground.
+pub fn make_noise(gs : &mut State, pos : Point, loudness : i32) {
+    if loudness < 1 { return; }
+    let heard = earshot(gs, pos, loudness);
+    for (id, mob) in gs.world.mobs.iter_mut() {
+        if let Some(mob_pos) = gs.world.positions.get(id) {
+            if heard[gs.map.point2d_to_index(*mob_pos) as usize] { mob.alerted_to = Some(pos); }
+        }
+    }
+}
+
+// A noise the player makes, softened by their stealth
+pub fn player_noise(gs : &mut State, loudness : i32) {
+    let pos = gs.player_position();
+    let loudness = loudness - player_stealth(gs);
+    make_noise(gs, pos, loudness);
+}
+
+// The player's stealth comes from what they have equipped
+pub fn player_stealth(gs : &State) -> i32 {
+    gs.player_inventory().equipped.iter()
+        .filter_map(|item| gs.world.equippables.get(*item))
+        .map(|equippable| equippable.stealth_bonus)
+        .sum()
+}
+
+pub fn unnoticed_chance(gs : &State) -> i32 {
+    let sneaking = if gs.player().sneaking { SNEAKING_UNNOTICED } else { 0 };
+    min(MAX_UNNOTICED, BASE_UNNOTICED + (player_stealth(gs) * UNNOTICED_PER_STEALTH) + sneaking)
+}
+
+// The tiles within loudness steps of pos, by the shortest way round
+fn earshot(gs : &State, pos : Point, loudness : i32) -> Vec<bool> {
+    let mut steps = vec![i32::MAX; gs.map.tiles.len()];
+    let mut open = BinaryHeap::new();
+    let start = gs.map.point2d_to_index(pos) as usize;
+    steps[start] = 0;
+    open.push(Reverse((0, start)));
+
+    while let Some(Reverse((distance, idx))) = open.pop() {
+        if distance > steps[idx] { continue; }
+        let (x, y) = (idx as i32 % gs.map.width, idx as i32 / gs.map.width);
+        for dy in -1 ..= 1 {
+            for dx in -1 ..= 1 {
+                let (nx, ny) = (x + dx, y + dy);
+                if (dx == 0 && dy == 0) || nx < 0 || ny < 0 || nx >= gs.map.width || ny >= gs.map.height { continue; }
+                let next = ((ny * gs.map.width) + nx) as usize;
+                let cost = match gs.map.tiles[next] {
+                    TileType::Wall => { continue }
+                    TileType::ClosedDoor | TileType::LockedDoor(_) => 1 + DOOR_MUFFLING,
+                    _ => 1
+                };
+                let reached = distance + cost;
+                if reached <= loudness && reached < steps[next] {
+                    steps[next] = reached;
+                    open.push(Reverse((reached, next)));
+                }
+            }
+        }
+    }
+    steps.iter().map(|s| *s <= loudness).collect()
+}
@@ -1,186 +1,240 @@
 use crate::rltk;
-use rltk::{RGB, Point, Rltk, field_of_view, Algorithm2D, VirtualKeyCode};
-use super::{fighter::Fighter, Inventory, BaseEntity, Combat, Map, ItemType, State, attack, TickType, inventory, item_effects, TileType, Particle};
-extern crate serde;
-use serde::{Serialize, Deserialize};
-
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Player {
-    pub position : Point,
-    pub glyph: u8,
-    pub fg : RGB,
-    pub visible_tiles : Vec<Point>,
-    pub fighter : Fighter,
-    pub inventory : Inventory,
-    pub dungeon_level : i32,
-    pub xp : i32,
-    pub level : i32
-}
+use rltk::{RGB, Point, Algorithm2D};
+use super::{ItemType, State, attack, TickType, inventory, item_effects, TileType, Particle, Command, LevelUpChoice, EntityId, systems, scheduler, trap, noise};
+
+// Handlers for gameplay
 
-impl Player {
-    pub fn new(x:i32, y:i32, glyph:u8, fg : RGB) -> Player {
-        Player{ 
-            position: Point::new(x, y), 
-            glyph, fg, 
-            visible_tiles: Vec::new(), 
-            fighter: Fighter::new(10, 0, 1, 0),
-            inventory: Inventory::new(26),
-            dungeon_level : 0,
-            xp : 0,
-            level : 1
+// Sneaking along takes longer than walking
+const SNEAK_MOVE_COST : i32 = scheduler::ACTION_COST * 2;
+
+#[derive(PartialEq)]
+pub enum PlayerTickResult { None, NextMap, PreviousMap }
+
+// What bumping in a direction came to
+enum MoveResult { Moved, Acted, Attack(EntityId), Nothing }
+
+pub fn player_action(gs : &mut State, command : Command) -> PlayerTickResult {
+    let mut energy_spent : Option<i32> = None;
+    let mut attack_target : Option<EntityId> = None;
+
+    match command {
+        Command::Move(delta_x, delta_y) => {
+            match move_player(gs, delta_x, delta_y) {
+                MoveResult::Moved => { energy_spent = Some(if gs.player().sneaking { SNEAK_MOVE_COST } else { scheduler::ACTION_COST }); }
+                MoveResult::Acted => { energy_spent = Some(scheduler::ACTION_COST); }
+                MoveResult::Attack(target) => { attack_target = Some(target); }
+                MoveResult::Nothing => {}
+            }
+        }
+        Command::Wait => { energy_spent = Some(scheduler::ACTION_COST); }
+        Command::CloseDoor => { energy_spent = close_doors(gs).then_some(scheduler::ACTION_COST); }
+        Command::Search => { trap::search(gs); energy_spent = Some(scheduler::ACTION_COST); }
+        Command::ToggleSneak => { toggle_sneaking(gs); }
+
+        // Items
+        Command::PickUp => { inventory::pickup(gs); energy_spent = Some(scheduler::ACTION_COST); }
+        Command::ShowUseMenu => { use_menu(gs); }
+        Command::ShowDropMenu => { drop_menu(gs); }
+        Command::ShowEquipMenu => { equip_menu(gs); }
+        Command::ShowUnequipMenu => { unequip_menu(gs); }
+        Command::UseItem(_) | Command::DropItem(_) | Command::EquipItem(_) | Command::UnequipItem(_) => {
+            inventory::item_command(gs, command);
         }
+
+        // Level Change
+        Command::Descend => {
+            if gs.map.tiles[gs.map.point2d_to_index(gs.player_position()) as usize] == TileType::Stairs {
+                return PlayerTickResult::NextMap;
+            } else {
+                gs.add_log_entry("You aren't on stairs".to_string());
+            }
+        }
+        Command::Ascend => {
+            if gs.map.tiles[gs.map.point2d_to_index(gs.player_position()) as usize] == TileType::UpStairs {
+                return PlayerTickResult::PreviousMap;
+            } else {
+                gs.add_log_entry("You aren't on stairs leading up".to_string());
+            }
+        }
+
+        // Character Info
+        Command::ShowCharacterInfo => { gs.game_state = TickType::CharacterMenu; }
+        Command::ShowHelp => { gs.game_state = TickType::HelpMenu; }
+        _ => {}
     }
 
-    pub fn copy_from_other_player(&mut self, other : &Player) {
-        self.glyph = other.glyph;
-        self.fg = other.fg;
-        self.fighter = other.fighter.clone();
-        self.inventory = other.inventory.clone();
-        self.dungeon_level = other.dungeon_level;
-        self.fighter.hp = self.fighter.max_hp;
-        // Not copying visible tiles or position, since this is intended for map transition
+    if let Some(target) = attack_target {
+        energy_spent = Some(scheduler::attack_cost(&gs.world, gs.player_id));
+        gs.vfx.push(Particle::new(*gs.world.positions.get(target).unwrap(), RGB::named(rltk::RED), RGB::named(rltk::BLACK), 176, 200.0));
+        let (xp, result) = attack(&mut gs.world, gs.player_id, target);
+        for s in result {
+            gs.add_log_entry(s.to_string());
+        }
+        noise::player_noise(gs, noise::COMBAT_NOISE);
+        systems::delete_the_dead(gs);
+        let p = gs.player_mut();
+        p.xp += xp;
     }
 
-    pub fn xp_to_level(&self) -> i32 {
-        200 + (self.level * 150)
+    if let Some(cost) = energy_spent {
+        gs.update_visibility();
+        end_turn(gs, cost);
     }
-}
 
-#[typetag::serde(name = "BEPlayer")]
-impl BaseEntity for Player {
-    fn get_position(&self) -> Point { self.position }
-    fn get_fg_color(&self) -> RGB { self.fg }
-    fn get_glyph(&self) -> u8 { self.glyph }
-    fn as_player(&self) -> Option<&Player> { Some(self) }
-    fn as_player_mut(&mut self) -> Option<&mut Player> { Some(self) }
-    fn as_combat(&mut self) -> Option<&mut Combat> { Some(self) }
-    fn plot_visibility(&mut self, map : &Map) {
-        self.visible_tiles = field_of_view(self.get_position(), 6, map);
-    }
-    fn get_tooltip_text(&self) -> String { "It's you!".to_string() }
-    fn get_name(&self) -> String { "Player".to_string() }
-    fn is_player(&self) -> bool { true }
+    PlayerTickResult::None
 }
 
-// Handlers for gameplay
-
-#[derive(PartialEq)]
-pub enum PlayerTickResult { None, NextMap }
+// The player has acted: pay for it, and let the rest of the world catch up
+pub fn end_turn(gs : &mut State, cost : i32) {
+    scheduler::spend_energy(&mut gs.world, gs.player_id, cost);
+    gs.game_state = TickType::EnemyTurn;
+}
 
-pub fn player_tick(gs : &mut State, ctx : &mut Rltk) -> PlayerTickResult {
+// Called at the start of the player's turn; returns true if the player gained a level
+pub fn check_level_up(gs : &mut State) -> bool {
     let player_ro = gs.player();
     if player_ro.xp > player_ro.xp_to_level() {
         let player_rw = gs.player_mut();
         player_rw.level += 1;
         let new_level = player_rw.level;
-        player_rw.fighter.hp = player_rw.fighter.max_hp;
+        let fighter = gs.player_fighter_mut();
+        fighter.hp = fighter.max_hp;
         gs.add_log_entry(format!("You are now level {}! Your wounds heal.", new_level));
         gs.game_state = TickType::LevelUpMenu;
-        return PlayerTickResult::None;
-    }
-
-    let mut turn_ended = false;
-    let mut attack_target : Option<usize> = None;
-
-    if let Some(key) = ctx.key {
-        match key {
-            VirtualKeyCode::Escape => { gs.save(); gs.game_state = TickType::MainMenu; }
-
-            // Numpad
-            VirtualKeyCode::Numpad8 => { attack_target = move_player(gs, 0, -1); turn_ended = true; }
-            VirtualKeyCode::Numpad4 => { attack_target = move_player(gs, -1, 0); turn_ended = true; }
-            VirtualKeyCode::Numpad6 => { attack_target = move_player(gs, 1, 0); turn_ended = true; }
-            VirtualKeyCode::Numpad2 => { attack_target = move_player(gs, 0, 1); turn_ended = true; }
-
-            VirtualKeyCode::Numpad7 => { attack_target = move_player(gs, -1, -1); turn_ended = true; }
-            VirtualKeyCode::Numpad9 => { attack_target = move_player(gs, 1, -1); turn_ended = true; }
-            VirtualKeyCode::Numpad1 => { attack_target = move_player(gs, -1, 1); turn_ended = true; }
-            VirtualKeyCode::Numpad3 => { attack_target = move_player(gs, 1, 1); turn_ended = true; }
-
-            // Cursors
-            VirtualKeyCode::Up => { attack_target = move_player(gs, 0, -1); turn_ended = true; }
-            VirtualKeyCode::Down => { attack_target = move_player(gs, 0, 1); turn_ended = true; }
-            VirtualKeyCode::Left => { attack_target = move_player(gs, -1, 0); turn_ended = true; }
-            VirtualKeyCode::Right => { attack_target = move_player(gs, 1, 0); turn_ended = true; }
-
-            // Wait
-            VirtualKeyCode::Numpad5 => { turn_ended = true; }
-            VirtualKeyCode::W => { turn_ended = true; }
-
-            // Items
-            VirtualKeyCode::G => { inventory::pickup(gs); turn_ended = true; }
-            VirtualKeyCode::U => { use_menu(gs); }
-            VirtualKeyCode::D => { drop_menu(gs); }
-            VirtualKeyCode::E => { equip_menu(gs); }
-            VirtualKeyCode::R => { unequip_menu(gs); }
-
-            // Level Change
-            VirtualKeyCode::Period => {  
-                if gs.map.tiles[gs.map.point2d_to_index(gs.player().position) as usize] == TileType::Stairs {
-                    return PlayerTickResult::NextMap;
-                } else {
-                    gs.add_log_entry("You aren't on stairs".to_string());
-                }
-            }
-
-            // Character Info
-            VirtualKeyCode::C => { gs.game_state = TickType::CharacterMenu; }
-            VirtualKeyCode::Slash => { gs.game_state = TickType::HelpMenu; }
-            _ => {}
-        }
+        return true;
     }
+    false
+}
 
-    if let Some(target) = attack_target {
-        gs.vfx.push(Particle::new(gs.entities[target].get_position(), RGB::named(rltk::RED), RGB::named(rltk::BLACK), 176, 200.0));
-        let player = gs.player_as_combat();
-        let (xp, result) = attack(player.get_name(), player.get_power(), gs.entities[target].as_combat().unwrap());
-        for s in result {
-            gs.add_log_entry(s.to_string());
+pub fn level_up(gs : &mut State, choice : LevelUpChoice) {
+    match choice {
+        LevelUpChoice::HitPoints => {
+            let fighter = gs.player_fighter_mut();
+            fighter.max_hp += 10;
+            fighter.hp = fighter.max_hp;
+        }
+        LevelUpChoice::Power => {
+            gs.player_fighter_mut().power += 1;
         }
-        gs.entities.retain(|e| !e.is_dead());
-        let p = gs.player_mut();
-        p.xp += xp;
-    }
-
-    if turn_ended {
-        gs.update_visibility();
-        gs.game_state = TickType::EnemyTurn; 
     }
-
-    PlayerTickResult::None
+    gs.game_state = TickType::PlayersTurn;
 }
 
-// Returns the ID of the target if we're attacking
-fn move_player(gs : &mut State, delta_x : i32, delta_y: i32) -> Option<usize> {
-    let mut result : Option<usize> = None;
-    let new_x = gs.player().position.x + delta_x;
-    let new_y = gs.player().position.y + delta_y;
+// Says whether the bump moved the player, attacked something, took the turn some
+// other way (opening a door, walking into a wall), or came to nothing at all
+fn move_player(gs : &mut State, delta_x : i32, delta_y: i32) -> MoveResult {
+    let mut result = MoveResult::Acted;
+    let player_pos = gs.player_position();
+    let new_x = player_pos.x + delta_x;
+    let new_y = player_pos.y + delta_y;
     let mut can_move : bool = true;
-    if new_x > 0 && new_x < 79 && new_y > 0 && new_y < 49 && gs.map.is_walkable(new_x, new_y) {
+    let new_idx = match gs.map.tile_idx(new_x, new_y) {
+        // Off the edge of the map is as good as a wall
+        None => { return result }
+        Some(idx) => idx
+    };
+    if let TileType::LockedDoor(lock) = gs.map.tiles[new_idx] {
+        if !unlock_door(gs, new_idx, lock) { result = MoveResult::Nothing; }
+    } else if gs.map.is_walkable(new_x, new_y) && gs.map.tiles[new_idx] == TileType::ClosedDoor {
+        // Bumping a closed door opens it, which takes the move
+        gs.map.tiles[new_idx] = TileType::OpenDoor;
+        gs.add_log_entry("You open the door.".to_string());
+        noise::player_noise(gs, noise::DOOR_NOISE);
+    } else if gs.map.is_walkable(new_x, new_y) {
 
         // Lets see if we are bumping a mob
         let new_pos = Point::new(new_x, new_y);
-        for (i,e) in gs.entities.iter_mut().enumerate() {
-            if e.get_position() == new_pos && e.blocks_tile() {
+        for (id,_blocks) in gs.world.blocks_tile.iter() {
+            if gs.world.positions.get(id) == Some(&new_pos) {
                 // Tile is indeed blocked
                 can_move = false;
-                if e.can_be_attacked() {
+                if gs.world.fighters.contains(id) {
                     // Attack it!
-                    result = Some(i);
+                    result = MoveResult::Attack(id);
                 }
             }
         }
 
         if can_move {
-            gs.player_mut().position.x = new_x;
-            gs.player_mut().position.y = new_y;
+            result = MoveResult::Moved;
+            gs.world.positions.insert(gs.player_id, new_pos);
+            noise::player_noise(gs, if gs.player().sneaking { noise::SNEAK_NOISE } else { noise::WALK_NOISE });
+            trap::trigger_trap_at(gs, gs.player_id, new_pos);
         }
     }
     result
 }
 
+// A locked door opens for its key, which is used up. The other doors on the same
+// lock are left closed but no longer locked. Returns false if the player has no
+// key, which costs nothing.
+fn unlock_door(gs : &mut State, idx : usize, lock : i32) -> bool {
+    let key = gs.player_inventory().items.iter().position(|item| {
+        gs.world.items.get(*item).map(|i| i.item_type) == Some(ItemType::Key{ lock })
+    });
+    match key {
+        None => {
+            gs.add_log_entry("The door is locked.".to_string());
+            false
+        }
+        Some(key_index) => {
+            let key = gs.player_inventory_mut().remove_item(key_index as i32);
+            let name = gs.world.name(key);
+            gs.world.delete_entity(key);
+            for tile in gs.map.tiles.iter_mut().filter(|t| **t == TileType::LockedDoor(lock)) {
+                *tile = TileType::ClosedDoor;
+            }
+            gs.map.tiles[idx] = TileType::OpenDoor;
+            gs.add_log_entry(format!("You unlock the door with the {}.", name));
+            noise::player_noise(gs, noise::DOOR_NOISE);
+            true
+        }
+    }
+}
+
+// Closes any open doors next to the player that nothing is standing in. Returns
+// true if a door was closed, which takes a turn.
+fn close_doors(gs : &mut State) -> bool {
+    let player_pos = gs.player_position();
+    let mut closed = false;
+    let mut obstructed = false;
+    for y in player_pos.y - 1 ..= player_pos.y + 1 {
+        for x in player_pos.x - 1 ..= player_pos.x + 1 {
+            let pos = Point::new(x, y);
+            if !gs.map.is_walkable(x, y) { continue; }
+            let idx = gs.map.point2d_to_index(pos) as usize;
+            if gs.map.tiles[idx] != TileType::OpenDoor { continue; }
+            // A hidden trap doesn't get in the way, or closing the door would give it away
+            if gs.world.positions.iter().any(|(id, p)| *p == pos && !trap::is_hidden(gs, id)) {
+                obstructed = true;
+                continue;
+            }
+            gs.map.tiles[idx] = TileType::ClosedDoor;
+            closed = true;
+        }
+    }
+
+    if closed {
+        gs.add_log_entry("You close the door.".to_string());
+        noise::player_noise(gs, noise::DOOR_NOISE);
+    } else if obstructed {
+        gs.add_log_entry("Something is in the way of the door.".to_string());
+    } else {
+        gs.add_log_entry("There is no open door next to you.".to_string());
+    }
+    closed
+}
+
+// Switching between walking and sneaking is free
+fn toggle_sneaking(gs : &mut State) {
+    let player = gs.player_mut();
+    player.sneaking = !player.sneaking;
+    let line = if player.sneaking { "You start sneaking." } else { "You stop sneaking." };
+    gs.add_log_entry(line.to_string());
+}
+
 fn use_menu(gs : &mut State) {
-    if gs.player().inventory.items.is_empty() {
+    if gs.player_inventory().items.is_empty() {
         gs.add_log_entry("You don't have any usable items.".to_string());
     } else {
         gs.game_state = TickType::UseMenu;
@@ -188,7 +242,7 @@ fn use_menu(gs : &mut State) {
 }
 
 fn drop_menu(gs : &mut State) {
-    if gs.player().inventory.items.is_empty() {
+    if gs.player_inventory().items.is_empty() {
         gs.add_log_entry("You don't have any items to drop!".to_string());
     } else {
         gs.game_state = TickType::DropMenu;
@@ -196,7 +250,7 @@ fn drop_menu(gs : &mut State) {
 }
 
 fn equip_menu(gs : &mut State) {
-    if gs.player().inventory.get_equippable_items().is_empty() {
+    if gs.player_inventory().get_equippable_items(&gs.world).is_empty() {
         gs.add_log_entry("You don't have any equippable items.".to_string());
     } else {
         gs.game_state = TickType::WieldMenu;
@@ -204,7 +258,7 @@ fn equip_menu(gs : &mut State) {
 }
 
 fn unequip_menu(gs : &mut State) {
-    if gs.player().inventory.equipped.is_empty() {
+    if gs.player_inventory().equipped.is_empty() {
         gs.add_log_entry("You don't have any equipped items.".to_string());
     } else {
         gs.game_state = TickType::UnequipMenu;
@@ -214,31 +268,44 @@ fn unequip_menu(gs : &mut State) {
 pub fn use_item(item_index : i32, gs : &mut State) -> Vec<String> {
     let mut result = Vec::new();
 
-    if gs.player().inventory.items[item_index as usize].requires_targeting_mode {
-        gs.game_state = TickType::TargetingItem;
-        gs.target_cell = gs.player().position;
-        gs.targeting_item = item_index;
-        result.push("Select a target tile".to_string());
-        return result;
-    }
+    let item = gs.player_inventory().items[item_index as usize];
+    let item_type = match gs.world.items.get(item) {
+        None => { return result }
+        Some(i) => {
+            if i.requires_targeting_mode() {
+                gs.game_state = TickType::TargetingItem;
+                gs.target_cell = gs.player_position();
+                gs.targeting_item = item_index;
+                result.push("Select a target tile".to_string());
+                return result;
+            }
+            i.item_type
+        }
+    };
 
-    let item_type = gs.player().inventory.items[item_index as usize].item_type;
+    let carried = gs.player_inventory().items.len();
     match item_type {
-        ItemType::HealthPotion => { item_effects::use_health_potion(item_index, gs, &mut result) }
-        ItemType::ZapScroll => { item_effects::use_zap_scroll(item_index, gs, &mut result) }
-        ItemType::ConfusionScroll => { item_effects::use_confusion_scroll(item_index, gs, &mut result) }
+        ItemType::Healing => { item_effects::use_health_potion(item_index, gs, &mut result) }
+        ItemType::Zap{ damage } => { item_effects::use_zap_scroll(item_index, damage, gs, &mut result) }
+        ItemType::Confusion{ turns } => { item_effects::use_confusion_scroll(item_index, turns, gs, &mut result) }
+        ItemType::Key{ .. } => { result.push("Walk into the locked door to use the key.".to_string()) }
         _ => {}
     }
 
-    gs.game_state = TickType::PlayersTurn;
+    // Only an item that actually got used up takes time
+    if gs.player_inventory().items.len() < carried {
+        end_turn(gs, scheduler::ACTION_COST);
+    } else {
+        gs.game_state = TickType::PlayersTurn;
+    }
 
     result
 }
 
 pub fn use_area_item(gs : &mut State) {
     let mut result = Vec::new(); 
-    let item_type = gs.player().inventory.items[gs.targeting_item as usize].item_type;
-    if let ItemType::FireballScroll = item_type {
-        item_effects::use_fireball_scroll(gs, &mut result)
+    let item = gs.player_inventory().items[gs.targeting_item as usize];
+    if let Some(ItemType::Fireball{ damage, radius }) = gs.world.items.get(item).map(|i| i.item_type) {
+        item_effects::use_fireball_scroll(damage, radius, gs, &mut result)
     }
 }
@@ -0,0 +1,273 @@
+use std::fs;
+use std::sync::Mutex;
+use std::collections::{BTreeMap, VecDeque};
+use crate::rltk;
+use rltk::{Point, Algorithm2D};
+use super::{Map, TileType, GameRng, DijkstraMap, RawError, dijkstra::UNREACHABLE};
+use super::raws::RAWS;
+use rand::Rng;
+use lazy_static::lazy_static;
+
+// Hand-made vaults, one per text file. A file starts with its tags, then a blank line,
+// then the vault itself:
+//
+//   depth: 2-8          (or "3" for one depth, "4+" for that depth and deeper)
+//   h: Mut Hound        (a letter that stands for a mob or item from the raws)
+//
+//   #####
+//   #h.>#
+//   ##.##
+//
+// '#' is wall, '.' floor, '>' the stairs down, and a space leaves the map as it was.
+// A vault needs a floor tile on its edge, where a corridor can join it to the level.
+pub const PREFAB_DIR : &str = "./prefabs";
+
+const VAULT_CHANCE : i32 = 50;
+const PLACEMENT_ATTEMPTS : i32 = 100;
+
+lazy_static! {
+    pub static ref PREFABS : Mutex<Vec<Prefab>> = Mutex::new(Vec::new());
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PrefabTile { Keep, Wall, Floor, Stairs, Spawn(char) }
+
+pub struct Prefab {
+    pub name : String,
+    min_depth : i32,
+    max_depth : Option<i32>,
+    width : i32,
+    height : i32,
+    tiles : Vec<PrefabTile>,
+    spawns : BTreeMap<char, String>
+}
+
+impl Prefab {
+    fn tile(&self, x : i32, y : i32) -> PrefabTile {
+        self.tiles[((y * self.width) + x) as usize]
+    }
+
+    fn allowed_at(&self, depth : i32) -> bool {
+        depth >= self.min_depth && self.max_depth.is_none_or(|max_depth| depth <= max_depth)
+    }
+
+    // Walkable tiles on the edge, with the direction that leads out of the vault
+    fn entrances(&self) -> Vec<(Point, Point)> {
+        let mut entrances = Vec::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if !is_open(self.tile(x, y)) { continue; }
+                if x == 0 { entrances.push((Point::new(x, y), Point::new(-1, 0))); }
+                else if x == self.width - 1 { entrances.push((Point::new(x, y), Point::new(1, 0))); }
+                else if y == 0 { entrances.push((Point::new(x, y), Point::new(0, -1))); }
+                else if y == self.height - 1 { entrances.push((Point::new(x, y), Point::new(0, 1))); }
+            }
+        }
+        entrances
+    }
+}
+
+fn is_open(tile : PrefabTile) -> bool {
+    matches!(tile, PrefabTile::Floor | PrefabTile::Stairs | PrefabTile::Spawn(_))
+}
+
+fn parse_depth(text : &str) -> Result<(i32, Option<i32>), String> {
+    let number = |s : &str| s.trim().parse::<i32>().map_err(|_| format!("\"{}\" is not a depth", text));
+    if let Some(min) = text.strip_suffix('+') {
+        return Ok((number(min)?, None));
+    }
+    match text.find('-') {
+        None => { let depth = number(text)?; Ok((depth, Some(depth))) }
+        Some(dash) => Ok((number(&text[..dash])?, Some(number(&text[dash+1..])?)))
+    }
+}
+
+pub fn parse_prefab(name : &str, text : &str) -> Result<Prefab, String> {
+    let mut lines = text.lines();
+    let mut depth : Option<(i32, Option<i32>)> = None;
+    let mut spawns = BTreeMap::new();
+
+    for line in &mut lines {
+        let line = line.trim();
+        if line.is_empty() { break; }
+        let colon = match line.find(':') {
+            None => { return Err(format!("\"{}\" should be a tag, like \"depth: 1-5\"", line)) }
+            Some(colon) => colon
+        };
+        let (key, value) = (line[..colon].trim(), line[colon+1..].trim());
+        if key == "depth" {
+            depth = Some(parse_depth(value)?);
+        } else if key.chars().count() == 1 && key.chars().all(|c| c.is_ascii_alphabetic()) {
+            spawns.insert(key.chars().next().unwrap(), value.to_string());
+        } else {
+            return Err(format!("unknown tag \"{}\"", key));
+        }
+    }
+    let (min_depth, max_depth) = match depth {
+        None => { return Err("needs a depth tag".to_string()) }
+        Some(depth) => depth
+    };
+    if min_depth < 0 || max_depth.is_some_and(|max_depth| max_depth < min_depth) {
+        return Err("has an impossible depth range".to_string());
+    }
+
+    let mut rows : Vec<&str> = lines.map(|l| l.trim_end()).collect();
+    while rows.last().is_some_and(|r| r.is_empty()) { rows.pop(); }
+    if rows.is_empty() { return Err("has no map".to_string()); }
+    let width = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0) as i32;
+    let height = rows.len() as i32;
+
+    let mut tiles = Vec::new();
+    for row in rows.iter() {
+        let mut count = 0;
+        for c in row.chars() {
+            tiles.push(match c {
+                ' ' => PrefabTile::Keep,
+                '#' => PrefabTile::Wall,
+                '.' => PrefabTile::Floor,
+                '>' => PrefabTile::Stairs,
+                c if spawns.contains_key(&c) => PrefabTile::Spawn(c),
+                c => { return Err(format!("'{}' in the map isn't a tile or a tagged letter", c)) }
+            });
+            count += 1;
+        }
+        for _i in count..width { tiles.push(PrefabTile::Keep); }
+    }
+
+    let prefab = Prefab{ name: name.to_string(), min_depth, max_depth, width, height, tiles, spawns };
+    if prefab.entrances().is_empty() { return Err("needs a floor tile on its edge to be entered by".to_string()); }
+    if prefab.tiles.iter().filter(|t| **t == PrefabTile::Stairs).count() > 1 { return Err("has more than one stairs down".to_string()); }
+    Ok(prefab)
+}
+
+// Reads every .txt file in the directory. The raws must already be loaded, since
+// vaults refer to mobs and items by name. A missing directory just means no vaults.
+pub fn load_prefabs(dir : &str) -> Result<(), RawError> {
+    let mut prefabs = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Err(_) => { *PREFABS.lock().unwrap() = prefabs; return Ok(()) }
+        Ok(entries) => entries
+    };
+    let mut paths : Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.extension().is_some_and(|e| e == "txt")).collect();
+    paths.sort();
+
+    let raws = RAWS.lock().unwrap();
+    for path in paths {
+        let path_name = path.to_string_lossy().to_string();
+        let text = fs::read_to_string(&path).map_err(|e| RawError::Io(path_name.clone(), e))?;
+        let name = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        let prefab = parse_prefab(&name, &text).map_err(|e| RawError::Invalid(path_name.clone(), e))?;
+        for spawn in prefab.spawns.values() {
+            if raws.mob(spawn).is_none() && raws.item(spawn).is_none() {
+                return Err(RawError::Invalid(path_name, format!("there is no mob or item called \"{}\"", spawn)));
+            }
+        }
+        prefabs.push(prefab);
+    }
+    *PREFABS.lock().unwrap() = prefabs;
+    Ok(())
+}
+
+// Maybe puts a vault for this depth into solid rock somewhere on the map, and digs a
+// corridor from it to the part of the level reachable from the start. Returns the
+// mobs and items the vault asks for.
+pub fn place_vault(map : &mut Map, start : Point, depth : i32, rng : &mut GameRng) -> Vec<(Point, String)> {
+    let prefabs = PREFABS.lock().unwrap();
+    let candidates : Vec<&Prefab> = prefabs.iter()
+        .filter(|p| p.allowed_at(depth) && p.width + 4 < map.width && p.height + 4 < map.height)
+        .collect();
+    if candidates.is_empty() || rng.gen_range(0, 100) >= VAULT_CHANCE { return Vec::new(); }
+    let prefab = candidates[rng.gen_range(0, candidates.len())];
+
+    for _i in 0..PLACEMENT_ATTEMPTS {
+        let x = rng.gen_range(2, map.width - prefab.width - 2);
+        let y = rng.gen_range(2, map.height - prefab.height - 2);
+        if fits(map, prefab, x, y) {
+            return stamp(map, prefab, Point::new(x, y), start, rng);
+        }
+    }
+    Vec::new()
+}
+
+// Only solid rock may be built over, with a border so the vault doesn't open onto a room
+fn fits(map : &Map, prefab : &Prefab, x : i32, y : i32) -> bool {
+    for ty in y - 1 ..= y + prefab.height {
+        for tx in x - 1 ..= x + prefab.width {
+            if map.tiles[((ty * map.width) + tx) as usize] != TileType::Wall { return false; }
+        }
+    }
+    true
+}
+
+fn stamp(map : &mut Map, prefab : &Prefab, at : Point, start : Point, rng : &mut GameRng) -> Vec<(Point, String)> {
+    let has_stairs = prefab.tiles.contains(&PrefabTile::Stairs);
+    if has_stairs {
+        // The vault's stairs replace the level's own
+        if let Some(stairs) = map.find_tile(TileType::Stairs) {
+            let idx = map.point2d_to_index(stairs) as usize;
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+
+    let mut spawns = Vec::new();
+    for y in 0..prefab.height {
+        for x in 0..prefab.width {
+            let pos = Point::new(at.x + x, at.y + y);
+            let idx = map.point2d_to_index(pos) as usize;
+            match prefab.tile(x, y) {
+                PrefabTile::Keep => {}
+                PrefabTile::Wall => { map.tiles[idx] = TileType::Wall; }
+                PrefabTile::Floor => { map.tiles[idx] = TileType::Floor; }
+                PrefabTile::Stairs => { map.tiles[idx] = TileType::Stairs; }
+                PrefabTile::Spawn(c) => {
+                    map.tiles[idx] = TileType::Floor;
+                    spawns.push((pos, prefab.spawns[&c].clone()));
+                }
+            }
+        }
+    }
+
+    let entrances = prefab.entrances();
+    let (entrance, outward) = entrances[rng.gen_range(0, entrances.len())];
+    let door = Point::new(at.x + entrance.x + outward.x, at.y + entrance.y + outward.y);
+    connect(map, prefab, at, door, start);
+    spawns
+}
+
+// Digs the shortest corridor from just outside the vault to anywhere the player can
+// already walk to, going around the vault rather than through it.
+fn connect(map : &mut Map, prefab : &Prefab, at : Point, door : Point, start : Point) {
+    map.refresh_blocked();
+    let reachable = DijkstraMap::new(map, &[start]);
+    let in_vault = |p : Point| p.x >= at.x && p.x < at.x + prefab.width && p.y >= at.y && p.y < at.y + prefab.height;
+
+    let size = (map.width * map.height) as usize;
+    let mut came_from : Vec<Option<i32>> = vec![None; size];
+    let door_idx = map.point2d_to_index(door);
+    came_from[door_idx as usize] = Some(door_idx);
+    let mut open = VecDeque::new();
+    open.push_back(door);
+
+    while let Some(pos) = open.pop_front() {
+        let idx = map.point2d_to_index(pos);
+        if reachable.distance(pos) != UNREACHABLE {
+            // Walk back to the door, turning rock into floor
+            let mut step = idx;
+            loop {
+                if map.tiles[step as usize] == TileType::Wall { map.tiles[step as usize] = TileType::Floor; }
+                if step == door_idx { break; }
+                step = came_from[step as usize].unwrap();
+            }
+            return;
+        }
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+            let next = Point::new(pos.x + dx, pos.y + dy);
+            if next.x < 1 || next.y < 1 || next.x > map.width - 2 || next.y > map.height - 2 || in_vault(next) { continue; }
+            let next_idx = map.point2d_to_index(next) as usize;
+            if came_from[next_idx].is_none() {
+                came_from[next_idx] = Some(idx);
+                open.push_back(next);
+            }
+        }
+    }
+}
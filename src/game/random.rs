@@ -1,13 +1,28 @@
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
 
-pub fn random_choice(table : Vec<(String, i32)>) -> String {
-    let mut rng = rand::thread_rng();
+// The one random number generator the game draws from. It lives in State (and
+// so in save games); the same seed always produces the same game.
+pub type GameRng = Pcg32;
 
-    let n = rng.gen_range(1,100);
+pub fn new_rng(seed : u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
+
+// Only used to pick a seed when the player didn't ask for one.
+pub fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+// Picks an entry with probability proportional to its weight
+pub fn random_choice(rng : &mut GameRng, table : Vec<(String, i32)>) -> String {
+    let total : i32 = table.iter().map(|(_opt, chance)| chance).sum();
+    if total < 1 { return table[0].0.to_string() }
+    let n = rng.gen_range(0, total);
     let mut running_sum = 0;
     for (opt,chance) in table.iter() {
         if n < chance+running_sum { return opt.to_string() }
         running_sum += chance;
     }
     table[0].0.to_string()
-}
\ No newline at end of file
+}
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::fs;
+use std::sync::Mutex;
+use std::collections::HashSet;
+use super::{ItemType, Equippable, LightSource, RangedAttack, MobAi, scheduler, components::opens_doors};
+use crate::rltk;
+use rltk::RGB;
+extern crate serde;
+use serde::Deserialize;
+use lazy_static::lazy_static;
+
+// Monster and item definitions, read from a JSON file at startup so that new
+// content doesn't need a rebuild.
+pub const RAW_FILE : &str = "./raws/spawns.json";
+
+lazy_static! {
+    pub static ref RAWS : Mutex<Raws> = Mutex::new(Raws{ mobs: Vec::new(), items: Vec::new() });
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Raws {
+    pub mobs : Vec<MobRaw>,
+    pub items : Vec<ItemRaw>
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MobRaw {
+    pub name : String,
+    pub glyph : u8,
+    pub colour : (u8, u8, u8),
+    pub fighter : FighterRaw,
+    #[serde(default = "normal_speed")]
+    pub speed : i32,
+    #[serde(default = "opens_doors")]
+    pub opens_doors : bool,
+    #[serde(default)]
+    pub light : Option<LightRaw>,
+    #[serde(default)]
+    pub ranged : Option<RangedRaw>,
+    #[serde(default)]
+    pub ai : MobAi,
+    pub spawn : SpawnRaw
+}
+
+fn normal_speed() -> i32 { scheduler::NORMAL_SPEED }
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FighterRaw {
+    pub max_hp : i32,
+    pub defense : i32,
+    pub power : i32,
+    pub xp_value : i32
+}
+
+// A glow around a mob, or around an item and whoever carries it
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LightRaw {
+    pub colour : (u8, u8, u8),
+    pub range : i32
+}
+
+impl LightRaw {
+    pub fn light_source(&self) -> LightSource {
+        LightSource{ colour: RGB::named(self.colour), range: self.range }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.range < 1 { return Err("needs a light range of at least 1".to_string()); }
+        Ok(())
+    }
+}
+
+// An attack a mob makes from a distance, and what the shot looks like in flight
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RangedRaw {
+    pub range : i32,
+    pub verb : String,
+    pub glyph : u8,
+    pub colour : (u8, u8, u8)
+}
+
+impl RangedRaw {
+    pub fn ranged_attack(&self) -> RangedAttack {
+        RangedAttack{ range: self.range, verb: self.verb.clone(), glyph: self.glyph, colour: RGB::named(self.colour) }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.range < 2 { return Err("needs a ranged attack range of at least 2".to_string()); }
+        if self.verb.is_empty() { return Err("needs a verb for its ranged attack".to_string()); }
+        Ok(())
+    }
+}
+
+// Where something can appear, and how common it is there. The weight starts at
+// `weight` on min_depth and changes by `weight_per_depth` each level below that.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnRaw {
+    pub min_depth : i32,
+    #[serde(default)]
+    pub max_depth : Option<i32>,
+    pub weight : i32,
+    #[serde(default)]
+    pub weight_per_depth : i32
+}
+
+impl SpawnRaw {
+    pub fn weight_at(&self, depth : i32) -> i32 {
+        if depth < self.min_depth { return 0; }
+        if let Some(max_depth) = self.max_depth {
+            if depth > max_depth { return 0; }
+        }
+        i32::max(0, self.weight + (self.weight_per_depth * (depth - self.min_depth)))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.min_depth < 0 { return Err("needs a min_depth of at least 0".to_string()); }
+        if let Some(max_depth) = self.max_depth {
+            if max_depth < self.min_depth { return Err("has a max_depth shallower than its min_depth".to_string()); }
+        }
+        if self.weight < 1 { return Err("needs a spawn weight of at least 1".to_string()); }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ItemRaw {
+    pub name : String,
+    pub glyph : u8,
+    pub colour : (u8, u8, u8),
+    #[serde(default)]
+    pub effect : Option<ItemType>,
+    #[serde(default)]
+    pub equippable : Option<Equippable>,
+    #[serde(default)]
+    pub light : Option<LightRaw>,
+    pub spawn : SpawnRaw
+}
+
+impl ItemRaw {
+    pub fn item_type(&self) -> ItemType {
+        match self.effect {
+            None => ItemType::Equipment,
+            Some(effect) => effect
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RawError {
+    Io(String, std::io::Error),
+    Parse(String, serde_json::Error),
+    Invalid(String, String)
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RawError::Io(path, e) => write!(f, "Unable to read {}: {}", path, e),
+            RawError::Parse(path, e) => write!(f, "{} is not valid: {}", path, e),
+            RawError::Invalid(path, msg) => write!(f, "{}: {}", path, msg)
+        }
+    }
+}
+
+impl Raws {
+    pub fn mob(&self, name : &str) -> Option<&MobRaw> {
+        self.mobs.iter().find(|m| m.name == name)
+    }
+
+    pub fn item(&self, name : &str) -> Option<&ItemRaw> {
+        self.items.iter().find(|i| i.name == name)
+    }
+
+    // Everything that can appear at this depth, with its weight there
+    pub fn mob_spawn_table(&self, depth : i32) -> Vec<(String, i32)> {
+        spawn_table(self.mobs.iter().map(|m| (&m.name, &m.spawn)), depth)
+    }
+
+    pub fn item_spawn_table(&self, depth : i32) -> Vec<(String, i32)> {
+        spawn_table(self.items.iter().map(|i| (&i.name, &i.spawn)), depth)
+    }
+
+    // Catches the mistakes the JSON parser can't: duplicate names, nonsense numbers,
+    // items that do nothing.
+    fn validate(&self) -> Result<(), String> {
+        if self.mobs.is_empty() { return Err("no mobs are defined".to_string()); }
+        if self.items.is_empty() { return Err("no items are defined".to_string()); }
+
+        let mut names = HashSet::new();
+        for mob in self.mobs.iter() {
+            if mob.name.is_empty() { return Err("a mob has an empty name".to_string()); }
+            if !names.insert(mob.name.clone()) { return Err(format!("\"{}\" is defined more than once", mob.name)); }
+            mob.spawn.validate().map_err(|e| format!("mob \"{}\" {}", mob.name, e))?;
+            if mob.fighter.max_hp < 1 { return Err(format!("mob \"{}\" needs a max_hp of at least 1", mob.name)); }
+            if mob.speed < 1 { return Err(format!("mob \"{}\" needs a speed of at least 1", mob.name)); }
+            if let Some(light) = &mob.light { light.validate().map_err(|e| format!("mob \"{}\" {}", mob.name, e))?; }
+            if let Some(ranged) = &mob.ranged { ranged.validate().map_err(|e| format!("mob \"{}\" {}", mob.name, e))?; }
+            if mob.ai.flee_below < 0 || mob.ai.flee_below > 100 { return Err(format!("mob \"{}\" needs a flee_below between 0 and 100", mob.name)); }
+            if mob.ai.keep_distance < 0 { return Err(format!("mob \"{}\" can't have a negative keep_distance", mob.name)); }
+        }
+
+        for item in self.items.iter() {
+            if item.name.is_empty() { return Err("an item has an empty name".to_string()); }
+            if !names.insert(item.name.clone()) { return Err(format!("\"{}\" is defined more than once", item.name)); }
+            item.spawn.validate().map_err(|e| format!("item \"{}\" {}", item.name, e))?;
+            if let Some(light) = &item.light { light.validate().map_err(|e| format!("item \"{}\" {}", item.name, e))?; }
+            match (item.item_type(), item.equippable.is_some()) {
+                // A lantern is useful just for being carried
+                (ItemType::Equipment, false) if item.light.is_some() => {}
+                (ItemType::Equipment, false) => { return Err(format!("item \"{}\" needs an effect, an equippable or a light section", item.name)); }
+                (ItemType::Equipment, true) => {}
+                (_, true) => { return Err(format!("item \"{}\" can't have both an effect and an equippable section", item.name)); }
+                (ItemType::Zap{ damage }, _) | (ItemType::Fireball{ damage, .. }, _) if damage < 1 => {
+                    return Err(format!("item \"{}\" needs a damage of at least 1", item.name));
+                }
+                (ItemType::Fireball{ radius, .. }, _) if radius < 1 => {
+                    return Err(format!("item \"{}\" needs a radius of at least 1", item.name));
+                }
+                (ItemType::Confusion{ turns }, _) if turns < 1 => {
+                    return Err(format!("item \"{}\" needs turns of at least 1", item.name));
+                }
+                (ItemType::Key{ .. }, _) => {
+                    return Err(format!("item \"{}\" can't be a key; keys are placed by the level builder", item.name));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn spawn_table<'a>(entries : impl Iterator<Item = (&'a String, &'a SpawnRaw)>, depth : i32) -> Vec<(String, i32)> {
+    entries
+        .map(|(name, spawn)| (name.clone(), spawn.weight_at(depth)))
+        .filter(|(_name, weight)| *weight > 0)
+        .collect()
+}
+
+pub fn load_raws(path : &str) -> Result<(), RawError> {
+    let data = fs::read_to_string(path).map_err(|e| RawError::Io(path.to_string(), e))?;
+    let raws : Raws = serde_json::from_str(&data).map_err(|e| RawError::Parse(path.to_string(), e))?;
+    raws.validate().map_err(|e| RawError::Invalid(path.to_string(), e))?;
+    *RAWS.lock().unwrap() = raws;
+    Ok(())
+}
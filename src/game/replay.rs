@@ -0,0 +1,139 @@
+use crate::rltk;
+use rltk::{Rltk, VirtualKeyCode};
+use super::{State, TickType, Command, session, gui};
+extern crate serde;
+use serde::{Serialize, Deserialize};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+const REPLAY_STEP_MS : f32 = 150.0;
+const FAST_FORWARD_STEPS_PER_FRAME : usize = 10;
+
+// A replay is the seed plus every command the player issued, in order. That is
+// all it takes to rebuild the exact game.
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub seed : u64,
+    pub commands : Vec<Command>
+}
+
+#[derive(Serialize, Deserialize)]
+struct ReplayHeader {
+    seed : u64
+}
+
+impl Replay {
+    pub fn new(seed : u64) -> Replay {
+        Replay{ seed, commands: Vec::new() }
+    }
+
+    // The file is JSON lines: a header with the seed, then one command per line.
+    pub fn load(path : &str) -> io::Result<Replay> {
+        let data = fs::read_to_string(path)?;
+        let mut lines = data.lines().filter(|l| !l.trim().is_empty());
+        let header : ReplayHeader = match lines.next() {
+            None => { return Err(io::Error::new(io::ErrorKind::InvalidData, "Replay file is empty")) }
+            Some(line) => { serde_json::from_str(line)? }
+        };
+        let mut replay = Replay::new(header.seed);
+        for line in lines {
+            replay.commands.push(serde_json::from_str(line)?);
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path : &str) -> io::Result<()> {
+        let mut f = File::create(path)?;
+        writeln!(f, "{}", serde_json::to_string(&ReplayHeader{ seed: self.seed })?)?;
+        for command in self.commands.iter() {
+            writeln!(f, "{}", serde_json::to_string(command)?)?;
+        }
+        Ok(())
+    }
+}
+
+// Appends commands to the replay file as they happen, so it survives a crash.
+pub struct ReplayRecorder {
+    path : String
+}
+
+impl ReplayRecorder {
+    pub fn start(path : &str, seed : u64) -> io::Result<ReplayRecorder> {
+        if let Some(dir) = Path::new(path).parent() { fs::create_dir_all(dir)?; }
+        Replay::new(seed).save(path)?;
+        Ok(ReplayRecorder{ path: path.to_string() })
+    }
+
+    // Carry on recording into an existing replay, e.g. when continuing a saved game
+    pub fn resume(path : &str) -> ReplayRecorder {
+        ReplayRecorder{ path: path.to_string() }
+    }
+
+    pub fn record(&self, command : Command) -> io::Result<()> {
+        let mut f = OpenOptions::new().append(true).open(&self.path)?;
+        writeln!(f, "{}", serde_json::to_string(&command)?)
+    }
+}
+
+pub struct ReplayPlayer {
+    pub replay : Replay,
+    pub position : usize,
+    pub paused : bool,
+    pub fast_forward : bool,
+    timer_ms : f32
+}
+
+impl ReplayPlayer {
+    pub fn new(replay : Replay) -> ReplayPlayer {
+        ReplayPlayer{ replay, position: 0, paused: false, fast_forward: false, timer_ms: 0.0 }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.position >= self.replay.commands.len()
+    }
+
+    fn step(&mut self, gs : &mut State) {
+        if !self.finished() {
+            session::apply_command(gs, self.replay.commands[self.position]);
+            self.position += 1;
+        }
+    }
+}
+
+// Replay mode: feeds recorded commands back into the game instead of reading input.
+// Space pauses, S (or period) steps while paused, F toggles fast-forward, Escape leaves.
+pub fn tick_replay(gs : &mut State, ctx : &mut Rltk) {
+    let mut player = match gs.replay.take() {
+        None => { return }
+        Some(player) => { player }
+    };
+
+    match ctx.key {
+        Some(VirtualKeyCode::Space) => { player.paused = !player.paused; }
+        Some(VirtualKeyCode::F) => { player.fast_forward = !player.fast_forward; }
+        Some(VirtualKeyCode::S) | Some(VirtualKeyCode::Period) => { if player.paused { player.step(gs); } }
+        Some(VirtualKeyCode::Escape) => {
+            gs.game_state = TickType::MainMenu;
+            return;
+        }
+        _ => {}
+    }
+
+    if !player.paused {
+        if player.fast_forward {
+            for _i in 0..FAST_FORWARD_STEPS_PER_FRAME { player.step(gs); }
+        } else {
+            player.timer_ms += ctx.frame_time_ms;
+            if player.timer_ms > REPLAY_STEP_MS {
+                player.timer_ms = 0.0;
+                player.step(gs);
+            }
+        }
+    }
+
+    gui::draw_replay_status(ctx, &player);
+    gs.replay = Some(player);
+}
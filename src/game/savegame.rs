@@ -0,0 +1,208 @@
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::{Write, BufRead, BufReader};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::{State, systems};
+extern crate serde;
+extern crate dirs;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+// Save files are two lines of JSON: a small header, {"version": N, "summary": {...}},
+// then the game itself (the State), so the main menu can list the slots without
+// reading whole games. When the saved game changes shape, bump SAVE_VERSION and add
+// a migration that upgrades the previous version's JSON, so older saves keep loading.
+pub const SAVE_VERSION : u64 = 1;
+
+// Each save slot is a file in the player's own data directory, so people sharing
+// a computer don't overwrite each other's games.
+pub const SAVE_SLOTS : usize = 5;
+
+// MIGRATIONS[n] upgrades the "game" of a version n+1 save to version n+2
+type Migration = fn(Value) -> Result<Value, String>;
+const MIGRATIONS : &[Migration] = &[];
+
+// What the main menu shows for a slot, without loading the whole game
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SlotSummary {
+    pub name : String,
+    pub depth : i32,
+    pub level : i32,
+    pub last_played : u64 // Seconds since the Unix epoch
+}
+
+pub enum SlotInfo { Empty, Saved(SlotSummary), Unreadable(String) }
+
+#[derive(Serialize, Deserialize)]
+struct SaveHeader {
+    version : u64,
+    summary : SlotSummary
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Damaged(String),
+    Incompatible(String)
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(e) => write!(f, "The saved game couldn't be read or written ({}).", e),
+            SaveError::Damaged(e) => write!(f, "The saved game is damaged ({}).", e),
+            SaveError::Incompatible(e) => write!(f, "The saved game is incompatible ({}).", e)
+        }
+    }
+}
+
+// e.g. ~/.local/share/rusty_roguelike on Linux; the working directory if there's no such thing
+pub fn save_dir() -> PathBuf {
+    match dirs::data_dir() {
+        None => PathBuf::from("./saves"),
+        Some(dir) => dir.join("rusty_roguelike")
+    }
+}
+
+fn slot_file(slot : usize) -> PathBuf {
+    save_dir().join(format!("slot{}.json", slot + 1))
+}
+
+// Each slot records its own replay alongside the save
+pub fn replay_file(slot : usize) -> String {
+    save_dir().join(format!("slot{}.replay.jsonl", slot + 1)).to_string_lossy().to_string()
+}
+
+pub fn list_slots() -> Vec<SlotInfo> {
+    (0..SAVE_SLOTS).map(slot_info).collect()
+}
+
+fn slot_info(slot : usize) -> SlotInfo {
+    let path = slot_file(slot);
+    if !path.exists() { return SlotInfo::Empty; }
+    let mut line = String::new();
+    let header : Result<SaveHeader, String> = File::open(&path)
+        .and_then(|f| BufReader::new(f).read_line(&mut line))
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::from_str(&line).map_err(|e| e.to_string()));
+    match header {
+        Ok(header) => SlotInfo::Saved(header.summary),
+        Err(e) => SlotInfo::Unreadable(e)
+    }
+}
+
+pub fn delete_save(slot : usize) {
+    let path = slot_file(slot);
+    if path.exists() { let _ = fs::remove_file(path); }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Writes to a temporary file and renames it over the save, so a crash part way
+// through never leaves a half-written save behind.
+pub fn save_game(gs : &State, slot : usize) -> Result<(), SaveError> {
+    let summary = SlotSummary{
+        name : gs.world.name(gs.player_id),
+        depth : gs.player().dungeon_level,
+        level : gs.player().level,
+        last_played : now()
+    };
+    let header = serde_json::to_string(&SaveHeader{ version: SAVE_VERSION, summary }).map_err(|e| SaveError::Damaged(e.to_string()))?;
+    let game = serde_json::to_string(gs).map_err(|e| SaveError::Damaged(e.to_string()))?;
+    let data = format!("{}\n{}", header, game);
+
+    fs::create_dir_all(save_dir()).map_err(SaveError::Io)?;
+    let save_file = slot_file(slot);
+    let temp_file = save_file.with_extension("json.tmp");
+    let mut f = File::create(&temp_file).map_err(SaveError::Io)?;
+    f.write_all(data.as_bytes()).map_err(SaveError::Io)?;
+    f.sync_all().map_err(SaveError::Io)?;
+    fs::rename(&temp_file, &save_file).map_err(SaveError::Io)?;
+    Ok(())
+}
+
+// Loads the save, upgrading it if it came from an older version. The file is only
+// removed once it has loaded successfully.
+pub fn load_game(slot : usize) -> Result<State, SaveError> {
+    let save_file = slot_file(slot);
+    let data = fs::read_to_string(&save_file).map_err(SaveError::Io)?;
+    let mut gs = parse_save(&data)?;
+    fs::remove_file(&save_file).map_err(SaveError::Io)?;
+
+    // Visibility and blocking aren't saved; work them out again
+    systems::map_indexing_system(&mut gs);
+    gs.update_visibility();
+    Ok(gs)
+}
+
+// Reads a save from its text, upgrading it if it came from an older version
+pub fn parse_save(data : &str) -> Result<State, SaveError> {
+    let (header, game) = data.split_once('\n').unwrap_or((data, ""));
+    let header : Value = serde_json::from_str(header).map_err(|e| SaveError::Damaged(e.to_string()))?;
+    let version = match header.get("version").and_then(|v| v.as_u64()) {
+        None => { return Err(SaveError::Incompatible("it was made before save files had a version".to_string())) }
+        Some(v) => v
+    };
+    if version > SAVE_VERSION {
+        return Err(SaveError::Incompatible(format!("it was made by a newer version of the game, save version {}", version)));
+    }
+    if version < 1 {
+        return Err(SaveError::Incompatible(format!("unknown save version {}", version)));
+    }
+
+    if game.trim().is_empty() { return Err(SaveError::Damaged("there is no game in it".to_string())); }
+    let game : Value = serde_json::from_str(game).map_err(|e| SaveError::Damaged(e.to_string()))?;
+    let game = migrate(game, version, MIGRATIONS)?;
+
+    serde_json::from_value(game).map_err(|e| SaveError::Damaged(e.to_string()))
+}
+
+// Brings the game from a version `version` save up to date, by running it through
+// every migration from that version onwards
+fn migrate(mut game : Value, version : u64, migrations : &[Migration]) -> Result<Value, SaveError> {
+    for migration in migrations.iter().skip((version - 1) as usize) {
+        game = migration(game).map_err(SaveError::Incompatible)?;
+    }
+    Ok(game)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{migrate, Migration, SaveError};
+    use serde_json::{json, Value};
+
+    // Two made-up migrations, from version 1 to 2 and from 2 to 3, that each leave a mark
+    fn one_to_two(mut game : Value) -> Result<Value, String> {
+        game["steps"].as_array_mut().unwrap().push(json!(2));
+        Ok(game)
+    }
+
+    fn two_to_three(mut game : Value) -> Result<Value, String> {
+        game["steps"].as_array_mut().unwrap().push(json!(3));
+        Ok(game)
+    }
+
+    fn refuse(_game : Value) -> Result<Value, String> {
+        Err("can't upgrade".to_string())
+    }
+
+    const FAKE_MIGRATIONS : &[Migration] = &[one_to_two, two_to_three];
+
+    #[test]
+    fn migrations_start_from_the_save_version() {
+        let game = json!({ "steps": [] });
+        assert_eq!(migrate(game.clone(), 1, FAKE_MIGRATIONS).unwrap(), json!({ "steps": [2, 3] }));
+        assert_eq!(migrate(game.clone(), 2, FAKE_MIGRATIONS).unwrap(), json!({ "steps": [3] }));
+        assert_eq!(migrate(game, 3, FAKE_MIGRATIONS).unwrap(), json!({ "steps": [] }));
+    }
+
+    #[test]
+    fn failed_migration_makes_save_incompatible() {
+        let result = migrate(json!({}), 1, &[refuse]);
+        assert!(matches!(result, Err(SaveError::Incompatible(e)) if e == "can't upgrade"));
+    }
+}
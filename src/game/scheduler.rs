@@ -0,0 +1,52 @@
+use super::{State, World, EntityId, mob, systems, ai_maps};
+use std::cmp::max;
+
+// Energy-based turn order. Every tick each entity gains its speed in energy; anyone
+// with at least ACTION_COST may act, and acting spends energy. At NORMAL_SPEED that
+// is one action per tick, so a speed 200 creature acts twice as often as the player.
+pub const NORMAL_SPEED : i32 = 100;
+pub const ACTION_COST : i32 = 100;
+
+pub fn can_act(world : &World, id : EntityId) -> bool {
+    match world.energy.get(id) {
+        None => false,
+        Some(energy) => energy.energy >= ACTION_COST
+    }
+}
+
+pub fn spend_energy(world : &mut World, id : EntityId, cost : i32) {
+    if let Some(energy) = world.energy.get_mut(id) {
+        energy.energy -= cost;
+    }
+}
+
+// Attacking costs more (or less) depending on what the attacker is wielding
+pub fn attack_cost(world : &World, id : EntityId) -> i32 {
+    let mut cost = ACTION_COST;
+    if let Some(inventory) = world.inventories.get(id) {
+        for item in inventory.equipped.iter() {
+            if let Some(equippable) = world.equippables.get(*item) { cost += equippable.attack_cost; }
+        }
+    }
+    max(1, cost)
+}
+
+// Runs the world forward until the player has the energy to act again (or is dead)
+pub fn run_until_player_turn(gs : &mut State) {
+    ai_maps::rebuild(gs);
+    systems::map_indexing_system(gs);
+
+    if !gs.world.energy.contains(gs.player_id) { return; }
+
+    while !can_act(&gs.world, gs.player_id) && !gs.player_fighter().dead {
+        for (_id, energy) in gs.world.energy.iter_mut() {
+            energy.energy += max(1, energy.speed);
+        }
+
+        for id in gs.world.mobs.ids() {
+            while gs.world.is_alive(id) && can_act(&gs.world, id) && !gs.player_fighter().dead {
+                mob::mob_act(gs, id);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use super::{State, TickType, TileType, Map, Command, player, inventory, scheduler, StoredLevel, EntityId, replay::Replay};
+
+// A game without a window: feed it commands, inspect the resulting State.
+pub struct GameSession {
+    pub state : State
+}
+
+impl GameSession {
+    pub fn new(seed : u64) -> GameSession {
+        GameSession{ state: State::new_game(seed) }
+    }
+
+    pub fn from_state(state : State) -> GameSession {
+        GameSession{ state }
+    }
+
+    // Rebuilds a recorded game by playing back all of its commands
+    pub fn from_replay(replay : &Replay) -> GameSession {
+        let mut session = GameSession::new(replay.seed);
+        for command in replay.commands.iter() {
+            session.issue(*command);
+        }
+        session
+    }
+
+    // Applies a command and, if it used up the player's turn, lets the monsters act.
+    pub fn issue(&mut self, command : Command) {
+        apply_command(&mut self.state, command);
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.state.game_state == TickType::GameOver
+    }
+
+    pub fn log(&self) -> &[String] {
+        &self.state.log
+    }
+}
+
+// Everything that changes the game world goes through here, no matter where the command came from.
+pub fn apply_command(gs : &mut State, command : Command) {
+    match gs.game_state {
+        TickType::PlayersTurn => {
+            let result = player::player_action(gs, command);
+            match result {
+                player::PlayerTickResult::NextMap => { let depth = gs.player().dungeon_level + 1; change_level(gs, depth); }
+                player::PlayerTickResult::PreviousMap => { let depth = gs.player().dungeon_level - 1; change_level(gs, depth); }
+                player::PlayerTickResult::None => {}
+            }
+        }
+        TickType::UseMenu | TickType::DropMenu | TickType::WieldMenu | TickType::UnequipMenu => {
+            match command {
+                Command::Cancel => { gs.game_state = TickType::PlayersTurn }
+                Command::UseItem(_) | Command::DropItem(_) | Command::EquipItem(_) | Command::UnequipItem(_) => {
+                    inventory::item_command(gs, command);
+                }
+                _ => {}
+            }
+        }
+        TickType::TargetingItem => {
+            match command {
+                Command::Cancel => { gs.game_state = TickType::PlayersTurn }
+                Command::Target(target) => { inventory::item_targeting(gs, target); }
+                _ => {}
+            }
+        }
+        TickType::LevelUpMenu => {
+            if let Command::LevelUp(choice) = command { player::level_up(gs, choice); }
+        }
+        TickType::CharacterMenu | TickType::HelpMenu => {
+            if command == Command::Cancel { gs.game_state = TickType::PlayersTurn; }
+        }
+        _ => {}
+    }
+
+    if gs.game_state == TickType::EnemyTurn {
+        end_turn(gs);
+    }
+}
+
+fn end_turn(gs : &mut State) {
+    scheduler::run_until_player_turn(gs);
+    // Lights may have moved with the mobs carrying them
+    gs.update_visibility();
+    gs.game_state = TickType::PlayersTurn;
+    if gs.player_fighter().dead {
+        gs.game_state = TickType::GameOver;
+    } else {
+        player::check_level_up(gs);
+    }
+}
+
+// Leaves the current level in the level store and moves the player to another one,
+// either as it was left or, on a first visit, freshly generated.
+fn change_level(gs : &mut State, depth : i32) {
+    let current_depth = gs.player().dungeon_level;
+
+    // Everything except the player and what they carry stays behind
+    let mut keep = vec![gs.player_id];
+    keep.extend(gs.player_inventory().items.iter().cloned());
+    keep.extend(gs.player_inventory().equipped.iter().cloned());
+    // Whatever a mob carries goes in the mob's own bundle, not one of its own
+    let carried : Vec<EntityId> = gs.world.inventories.iter()
+        .flat_map(|(_owner, inventory)| inventory.items.iter().chain(inventory.equipped.iter()).cloned().collect::<Vec<EntityId>>())
+        .collect();
+    let mut left_behind = Vec::new();
+    for id in gs.world.entities() {
+        if keep.contains(&id) || carried.contains(&id) || !gs.world.is_alive(id) { continue; }
+        left_behind.push(gs.world.extract_entity(id));
+    }
+    let map = std::mem::replace(&mut gs.map, Map::new(0, 0));
+    gs.levels.store(current_depth, StoredLevel{ map, entities: left_behind });
+
+    match gs.levels.take(depth) {
+        Some(level) => {
+            gs.map = level.map;
+            for bundle in level.entities {
+                gs.world.insert_bundle(bundle);
+            }
+
+            // Arrive on the stairs that lead back where we came from
+            let arrival = if depth > current_depth { TileType::UpStairs } else { TileType::Stairs };
+            if let Some(pos) = gs.map.find_tile(arrival) {
+                gs.world.positions.insert(gs.player_id, pos);
+            }
+            gs.player_mut().dungeon_level = depth;
+            gs.update_visibility();
+
+            if depth > current_depth {
+                gs.add_log_entry(format!("You descend back to depth {}.", depth));
+            } else {
+                gs.add_log_entry(format!("You climb back up to depth {}.", depth));
+            }
+        }
+        None => {
+            let fighter = gs.player_fighter_mut();
+            fighter.hp = fighter.max_hp;
+
+            gs.new_level(depth);
+            gs.add_log_entry("You descend to the next level, and take a moment to rest.".to_string());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use super::{ Map, Rect, TileType, GameRng, map_builder, map_builder::{MapBuilder, SpawnRegion} };
+use crate::rltk;
+use rltk::Point;
+use rand::Rng;
+
+const ROOM_MAX_SIZE : i32 = 10;
+const ROOM_MIN_SIZE : i32 = 6;
+// One attempt at placing a room per this many tiles, so bigger maps get as many rooms for their size
+const TILES_PER_ROOM : i32 = 115;
+
+// Rooms scattered at random, each joined to the one placed before it by an L-shaped corridor
+pub struct SimpleMapBuilder {
+    map : Map,
+    rooms : Vec<Rect>
+}
+
+impl SimpleMapBuilder {
+    pub fn new(width : i32, height : i32) -> SimpleMapBuilder {
+        SimpleMapBuilder{ map: Map::new(width, height), rooms: Vec::new() }
+    }
+}
+
+impl MapBuilder for SimpleMapBuilder {
+    fn name(&self) -> &'static str { "rooms" }
+
+    fn build_map(&mut self, rng : &mut GameRng) {
+        let max_rooms = (self.map.width * self.map.height) / TILES_PER_ROOM;
+        for _i in 1..max_rooms {
+            let w = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE);
+            let h = rng.gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE);
+            let x = rng.gen_range(1, self.map.width - w - 1);
+            let y = rng.gen_range(1, self.map.height - h - 1);
+
+            let room_candidate = Rect::new(x, y, x+w, y+h);
+
+            let mut collides = false;
+            for room in self.rooms.iter() {
+                if room_candidate.intersect(room) {
+                    collides = true;
+                }
+            }
+
+            if !collides {
+                map_builder::apply_room(&mut self.map, &room_candidate);
+
+                if !self.rooms.is_empty() {
+                    let (new_x, new_y) = room_candidate.center();
+                    let (prev_x, prev_y) = self.rooms[self.rooms.len()-1].center();
+                    if rng.gen_range(0, 2) == 1 {
+                        map_builder::apply_horizontal_tunnel(&mut self.map, prev_x, new_x, prev_y);
+                        map_builder::apply_vertical_tunnel(&mut self.map, prev_y, new_y, new_x);
+                    } else {
+                        map_builder::apply_vertical_tunnel(&mut self.map, prev_y, new_y, prev_x);
+                        map_builder::apply_horizontal_tunnel(&mut self.map, prev_x, new_x, new_y);
+                    }
+                }
+
+                self.rooms.push(room_candidate);
+            }
+        }
+
+        map_builder::place_doors(&mut self.map, &self.rooms, rng);
+        let stairs = self.stairs_position();
+        self.map.tiles[((stairs.y * self.map.width) + stairs.x) as usize] = TileType::Stairs;
+    }
+
+    fn map(&self) -> Map {
+        self.map.clone()
+    }
+
+    fn starting_position(&self) -> Point {
+        let (x, y) = self.rooms[0].center();
+        Point::new(x, y)
+    }
+
+    fn stairs_position(&self) -> Point {
+        let (x, y) = self.rooms[self.rooms.len()-1].center();
+        Point::new(x, y)
+    }
+
+    fn rooms(&self) -> Vec<Rect> {
+        self.rooms.clone()
+    }
+
+    fn spawn_regions(&self) -> Vec<SpawnRegion> {
+        self.rooms.iter().skip(1).map(SpawnRegion::from_rect).collect()
+    }
+}
@@ -0,0 +1,117 @@
+use crate::rltk;
+use rltk::{RGB, Point};
+use super::{World, EntityId, Renderable, Viewshed, Name, Fighter, Inventory, Item, ItemType, Mob, AiState, Player, BlocksTile, Energy, Trap, TrapType, LightSource, random_choice, GameRng, scheduler};
+use super::raws::{RAWS, MobRaw, ItemRaw};
+
+// Each kind of entity is just a set of components; these functions assemble them.
+// Mobs and items are built from their raw definitions.
+
+const TORCH_RANGE : i32 = 5;
+
+pub fn player(world : &mut World, x : i32, y : i32) -> EntityId {
+    let id = world.create_entity();
+    world.positions.insert(id, Point::new(x, y));
+    world.renderables.insert(id, Renderable{ glyph: 64, fg: RGB::named(rltk::YELLOW), render_order: 0 });
+    world.viewsheds.insert(id, Viewshed::new(6));
+    world.names.insert(id, Name{ name: "Player".to_string() });
+    world.fighters.insert(id, Fighter::new(10, 0, 1, 0));
+    world.inventories.insert(id, Inventory::new(26));
+    world.players.insert(id, Player{ dungeon_level: 0, xp: 0, level: 1, sneaking: false });
+    world.energy.insert(id, Energy{ speed: scheduler::NORMAL_SPEED, energy: scheduler::ACTION_COST });
+    id
+}
+
+// Nothing spawns if no mob is allowed at this depth
+pub fn random_mob(world : &mut World, x : i32, y : i32, depth : i32, rng : &mut GameRng) -> Option<EntityId> {
+    let raws = RAWS.lock().unwrap();
+    let table = raws.mob_spawn_table(depth);
+    if table.is_empty() { return None; }
+    let choice = random_choice(rng, table);
+    Some(mob(world, x, y, raws.mob(&choice).unwrap()))
+}
+
+// A particular mob or item, by its name in the raws (e.g. one placed by a vault)
+pub fn named(world : &mut World, x : i32, y : i32, name : &str) -> Option<EntityId> {
+    let raws = RAWS.lock().unwrap();
+    if let Some(raw) = raws.mob(name) { return Some(mob(world, x, y, raw)); }
+    raws.item(name).map(|raw| item(world, x, y, raw))
+}
+
+fn mob(world : &mut World, x : i32, y : i32, raw : &MobRaw) -> EntityId {
+    let id = world.create_entity();
+    world.positions.insert(id, Point::new(x, y));
+    world.renderables.insert(id, Renderable{ glyph: raw.glyph, fg: RGB::named(raw.colour), render_order: 1 });
+    world.viewsheds.insert(id, Viewshed::new(6));
+    world.names.insert(id, Name{ name: raw.name.clone() });
+    world.fighters.insert(id, Fighter::new(raw.fighter.max_hp, raw.fighter.defense, raw.fighter.power, raw.fighter.xp_value));
+    world.mobs.insert(id, Mob{ opens_doors: raw.opens_doors, alerted_to: None, ai: raw.ai, memory: None, state: AiState::default(), known_traps: Vec::new() });
+    world.blocks_tile.insert(id, BlocksTile{});
+    world.energy.insert(id, Energy{ speed: raw.speed, energy: 0 });
+    if let Some(light) = &raw.light {
+        world.lights.insert(id, light.light_source());
+    }
+    if let Some(ranged) = &raw.ranged {
+        world.ranged.insert(id, ranged.ranged_attack());
+    }
+    id
+}
+
+// Keys aren't in the raws, since each one is made for a particular lock
+pub fn key(world : &mut World, x : i32, y : i32, lock : i32) -> EntityId {
+    let id = world.create_entity();
+    world.positions.insert(id, Point::new(x, y));
+    world.renderables.insert(id, Renderable{ glyph: 45, fg: RGB::named(rltk::GOLD), render_order: 2 });
+    world.names.insert(id, Name{ name: format!("Depth {} Key", lock) });
+    world.items.insert(id, Item{ item_type: ItemType::Key{ lock } });
+    id
+}
+
+pub fn torch(world : &mut World, x : i32, y : i32) -> EntityId {
+    let id = world.create_entity();
+    world.positions.insert(id, Point::new(x, y));
+    world.renderables.insert(id, Renderable{ glyph: 15, fg: RGB::named(rltk::ORANGE), render_order: 3 });
+    world.names.insert(id, Name{ name: "Wall Torch".to_string() });
+    world.lights.insert(id, LightSource{ colour: RGB::from_f32(1.0, 0.7, 0.3), range: TORCH_RANGE });
+    id
+}
+
+// A raw item, straight into the owner's backpack (e.g. what the player starts with)
+pub fn carried(world : &mut World, owner : EntityId, name : &str) -> Option<EntityId> {
+    let raws = RAWS.lock().unwrap();
+    let id = item(world, 0, 0, raws.item(name)?);
+    world.positions.remove(id);
+    world.inventories.get_mut(owner)?.items.push(id);
+    Some(id)
+}
+
+pub fn trap(world : &mut World, x : i32, y : i32, trap_type : TrapType) -> EntityId {
+    let id = world.create_entity();
+    world.positions.insert(id, Point::new(x, y));
+    world.renderables.insert(id, Renderable{ glyph: 94, fg: RGB::named(rltk::RED), render_order: 3 });
+    world.names.insert(id, Name{ name: trap_type.name().to_string() });
+    world.traps.insert(id, Trap{ trap_type, revealed: false });
+    id
+}
+
+pub fn random_item(world : &mut World, x : i32, y : i32, depth : i32, rng : &mut GameRng) -> Option<EntityId> {
+    let raws = RAWS.lock().unwrap();
+    let table = raws.item_spawn_table(depth);
+    if table.is_empty() { return None; }
+    let choice = random_choice(rng, table);
+    Some(item(world, x, y, raws.item(&choice).unwrap()))
+}
+
+fn item(world : &mut World, x : i32, y : i32, raw : &ItemRaw) -> EntityId {
+    let id = world.create_entity();
+    world.positions.insert(id, Point::new(x, y));
+    world.renderables.insert(id, Renderable{ glyph: raw.glyph, fg: RGB::named(raw.colour), render_order: 2 });
+    world.names.insert(id, Name{ name: raw.name.clone() });
+    world.items.insert(id, Item{ item_type: raw.item_type() });
+    if let Some(equippable) = raw.equippable {
+        world.equippables.insert(id, equippable);
+    }
+    if let Some(light) = &raw.light {
+        world.lights.insert(id, light.light_source());
+    }
+    id
+}
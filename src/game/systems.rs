@@ -0,0 +1,43 @@
+use crate::rltk;
+use rltk::Algorithm2D;
+use super::{State, EntityId, lighting};
+
+// Relights the level, then recalculates every viewshed and what the player can see on the map
+pub fn visibility_system(gs : &mut State) {
+    lighting::lighting_system(gs);
+    let map = &gs.map;
+    for (id, viewshed) in gs.world.viewsheds.iter_mut() {
+        if let Some(pos) = gs.world.positions.get(id) {
+            viewshed.visible_tiles = lighting::visible_tiles(map, *pos, viewshed.range);
+        }
+    }
+
+    let vt = gs.player_viewshed().visible_tiles.clone();
+    gs.map.set_visibility(&vt);
+}
+
+// Builds the master map of unavailable tiles: walls, plus anything that blocks
+pub fn map_indexing_system(gs : &mut State) {
+    gs.map.refresh_blocked();
+    for (id, _blocks) in gs.world.blocks_tile.iter() {
+        if let Some(pos) = gs.world.positions.get(id) {
+            gs.map.set_tile_blocked(gs.map.point2d_to_index(*pos));
+        }
+    }
+}
+
+// Removes everything that died this turn, apart from the player (whose death ends the game)
+pub fn delete_the_dead(gs : &mut State) {
+    let dead : Vec<EntityId> = gs.world.fighters.iter()
+        .filter(|(id, fighter)| fighter.dead && *id != gs.player_id)
+        .map(|(id, _fighter)| id)
+        .collect();
+    for id in dead {
+        // Whatever a mob was carrying falls where it died
+        if let (Some(pos), Some(inventory)) = (gs.world.positions.get(id).cloned(), gs.world.inventories.get_mut(id)) {
+            let carried : Vec<EntityId> = inventory.items.drain(..).chain(inventory.equipped.drain(..)).collect();
+            for item in carried { gs.world.positions.insert(item, pos); }
+        }
+        gs.world.delete_entity(id);
+    }
+}
@@ -1,7 +1,9 @@
 extern crate serde;
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize, PartialEq)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
 pub enum TileType {
-    Wall, Floor, Stairs
+    Wall, Floor, Stairs, UpStairs, ClosedDoor, OpenDoor,
+    // Opened by the key with the same lock number
+    LockedDoor(i32)
 }
@@ -0,0 +1,174 @@
+use crate::rltk;
+use rltk::{Point, Algorithm2D};
+use super::{State, EntityId, systems, scheduler, lighting, noise};
+use rand::Rng;
+extern crate serde;
+use serde::{Serialize, Deserialize};
+
+const DART_DAMAGE : i32 = 2;
+const PIT_DAMAGE : i32 = 3;
+// Climbing out of a pit takes this much energy on top of the move that fell in
+const PIT_CLIMB_COST : i32 = scheduler::ACTION_COST * 2;
+const SEARCH_RANGE : f32 = 2.5;
+// Out of 100, for each hidden trap in range on each search
+const SEARCH_CHANCE : i32 = 40;
+
+#[derive(PartialEq, Clone, Copy, Serialize, Deserialize, Debug)]
+pub enum TrapType {
+    Dart { damage : i32 },
+    Pit { damage : i32 },
+    Alarm,
+    Teleport
+}
+
+impl TrapType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrapType::Dart{..} => "Dart Trap",
+            TrapType::Pit{..} => "Pit Trap",
+            TrapType::Alarm => "Alarm Trap",
+            TrapType::Teleport => "Teleport Trap"
+        }
+    }
+}
+
+// A trap lies hidden on the floor until the player finds it or walks into it. Mobs
+// are no better at spotting them: they keep clear of revealed traps, and of any
+// they have seen go off.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Trap {
+    pub trap_type : TrapType,
+    pub revealed : bool
+}
+
+// The kinds of trap that can appear at a depth; the nastier ones come later
+pub fn trap_types(depth : i32) -> Vec<TrapType> {
+    let mut types = vec![TrapType::Dart{ damage: DART_DAMAGE }, TrapType::Pit{ damage: PIT_DAMAGE + depth / 2 }];
+    if depth > 0 { types.push(TrapType::Alarm); }
+    if depth > 1 { types.push(TrapType::Teleport); }
+    types
+}
+
+pub fn is_hidden(gs : &State, id : EntityId) -> bool {
+    gs.world.traps.get(id).is_some_and(|trap| !trap.revealed)
+}
+
+// Whether the mob knows there's a trap at pos
+pub fn known_trap_at(gs : &State, mob_id : EntityId, pos : Point) -> bool {
+    match trap_at(gs, pos).and_then(|id| gs.world.traps.get(id)) {
+        None => false,
+        Some(trap) => trap.revealed || gs.world.mobs.get(mob_id).is_some_and(|mob| mob.known_traps.contains(&pos))
+    }
+}
+
+fn trap_at(gs : &State, pos : Point) -> Option<EntityId> {
+    gs.world.traps.iter()
+        .find(|(id, _trap)| gs.world.positions.get(*id) == Some(&pos))
+        .map(|(id, _trap)| id)
+}
+
+// Sets off whatever trap is at pos, if any, on the player or mob that just stepped there
+pub fn trigger_trap_at(gs : &mut State, victim : EntityId, pos : Point) {
+    let trap_id = match trap_at(gs, pos) {
+        None => { return }
+        Some(id) => id
+    };
+    let is_player = victim == gs.player_id;
+    let seen = is_player || gs.map.is_tile_visible(pos);
+    let victim_name = gs.world.name(victim);
+
+    // The player only learns where the trap is if they saw it go off, and the same goes for mobs
+    for (id, mob) in gs.world.mobs.iter_mut() {
+        let saw_it = id == victim || gs.world.viewsheds.get(id).is_some_and(|viewshed| viewshed.visible_tiles.contains(&pos));
+        if saw_it && !mob.known_traps.contains(&pos) { mob.known_traps.push(pos); }
+    }
+    let trap = gs.world.traps.get_mut(trap_id).unwrap();
+    if seen { trap.revealed = true; }
+    let trap_type = trap.trap_type;
+    if is_player {
+        gs.add_log_entry(format!("You trigger a {}!", trap_type.name().to_lowercase()));
+    } else if seen {
+        gs.add_log_entry(format!("{} triggers a {}!", victim_name, trap_type.name().to_lowercase()));
+    }
+
+    match trap_type {
+        TrapType::Dart{ damage } => {
+            if seen { gs.add_log_entry(format!("A dart hits {} for {} points of damage.", victim_name, damage)); }
+            hurt(gs, victim, damage);
+        }
+        TrapType::Pit{ damage } => {
+            if seen { gs.add_log_entry(format!("{} falls into a pit, taking {} points of damage.", victim_name, damage)); }
+            hurt(gs, victim, damage);
+            scheduler::spend_energy(&mut gs.world, victim, PIT_CLIMB_COST);
+        }
+        TrapType::Alarm => {
+            // Mobs that hear it come to see what set it off
+            gs.add_log_entry("An alarm bell rings out!".to_string());
+            noise::make_noise(gs, pos, noise::ALARM_NOISE);
+        }
+        TrapType::Teleport => {
+            teleport(gs, victim, pos);
+            if seen { gs.add_log_entry(format!("{} vanishes in a flash of light!", victim_name)); }
+        }
+    }
+}
+
+fn hurt(gs : &mut State, victim : EntityId, damage : i32) {
+    let victim_name = gs.world.name(victim);
+    if let Some(fighter) = gs.world.fighters.get_mut(victim) {
+        fighter.take_damage(damage);
+        if fighter.hp < 1 {
+            fighter.kill();
+            if victim != gs.player_id { gs.add_log_entry(format!("{} is dead.", victim_name)); }
+        }
+    }
+    systems::delete_the_dead(gs);
+}
+
+// Moves the victim to a random open spot on the level
+fn teleport(gs : &mut State, victim : EntityId, from : Point) {
+    let mut destinations = Vec::new();
+    for y in 1 .. gs.map.height - 1 {
+        for x in 1 .. gs.map.width - 1 {
+            let pos = Point::new(x, y);
+            if gs.map.is_walkable(x, y) && !gs.map.is_tile_blocked(gs.map.point2d_to_index(pos)) && trap_at(gs, pos).is_none() {
+                destinations.push(pos);
+            }
+        }
+    }
+    if destinations.is_empty() { return; }
+    let to = destinations[gs.rng.gen_range(0, destinations.len())];
+
+    let from_idx = gs.map.point2d_to_index(from);
+    let to_idx = gs.map.point2d_to_index(to);
+    gs.map.clear_tile_blocked(from_idx);
+    gs.map.set_tile_blocked(to_idx);
+    gs.world.positions.insert(victim, to);
+    if let Some(viewshed) = gs.world.viewsheds.get_mut(victim) {
+        viewshed.visible_tiles = lighting::visible_tiles(&gs.map, to, viewshed.range);
+    }
+}
+
+// Looks around the player for hidden traps. Each one close by and in sight may be found.
+pub fn search(gs : &mut State) {
+    let player_pos = gs.player_position();
+    let hidden : Vec<(EntityId, Point)> = gs.world.traps.iter()
+        .filter(|(_id, trap)| !trap.revealed)
+        .filter_map(|(id, _trap)| gs.world.positions.get(id).map(|pos| (id, *pos)))
+        .filter(|(_id, pos)| gs.map.is_tile_visible(*pos) && rltk::DistanceAlg::Pythagoras.distance2d(player_pos, *pos) <= SEARCH_RANGE)
+        .collect();
+
+    let mut found = false;
+    for (id, _pos) in hidden {
+        if gs.rng.gen_range(0, 100) < SEARCH_CHANCE {
+            let trap = gs.world.traps.get_mut(id).unwrap();
+            trap.revealed = true;
+            let name = trap.trap_type.name().to_lowercase();
+            gs.add_log_entry(format!("You find a {}!", name));
+            found = true;
+        }
+    }
+    if !found {
+        gs.add_log_entry("You search the area, but find nothing.".to_string());
+    }
+}
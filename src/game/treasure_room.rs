@@ -0,0 +1,86 @@
+use super::{ Map, Rect, TileType, World, GameRng, DijkstraMap, dijkstra::UNREACHABLE, spawner, map_builder::SpawnRegion };
+use crate::rltk;
+use rltk::Point;
+use rand::Rng;
+
+// Out of 100, for a level that has a room fit to be locked up
+const TREASURE_ROOM_CHANCE : i32 = 50;
+// The loot is rolled as if it were this many levels deeper
+const TREASURE_DEPTH_BONUS : i32 = 3;
+const MIN_TREASURE : i32 = 2;
+const MAX_TREASURE : i32 = 4;
+
+// A room whose doors are all locked, and where the key to them was left
+pub struct TreasureRoom {
+    pub lock : i32,
+    pub key : Point,
+    pub loot : SpawnRegion
+}
+
+// Picks a room that is only entered through doors, locks them all, and leaves the key
+// somewhere in the spawn regions that can be reached without going through them. The
+// room's tiles are taken out of the spawn regions, since the loot goes there instead.
+pub fn seal_treasure_room(map : &mut Map, rooms : &[Rect], start : Point, spawn_regions : &mut Vec<SpawnRegion>, depth : i32, rng : &mut GameRng) -> Option<TreasureRoom> {
+    if rng.gen_range(0, 100) >= TREASURE_ROOM_CHANCE { return None; }
+    let stairs = map.find_tile(TileType::Stairs)?;
+
+    let candidates : Vec<(Rect, Vec<usize>)> = rooms.iter()
+        .filter(|room| !in_room(room, start) && !in_room(room, stairs))
+        .map(|room| (*room, openings(map, room)))
+        .filter(|(_room, doors)| !doors.is_empty() && doors.iter().all(|idx| matches!(map.tiles[*idx], TileType::ClosedDoor | TileType::OpenDoor)))
+        .collect();
+    if candidates.is_empty() { return None; }
+    let (room, doors) = candidates[rng.gen_range(0, candidates.len())].clone();
+
+    for idx in doors.iter() { map.tiles[*idx] = TileType::LockedDoor(depth); }
+    map.refresh_blocked();
+    let reachable = DijkstraMap::new(map, &[start]);
+    let key_spots : Vec<Point> = spawn_regions.iter()
+        .flat_map(|region| region.tiles.iter())
+        .filter(|pos| !in_room(&room, **pos) && reachable.distance(**pos) != UNREACHABLE)
+        .cloned()
+        .collect();
+    if key_spots.is_empty() {
+        for idx in doors.iter() { map.tiles[*idx] = TileType::ClosedDoor; }
+        return None;
+    }
+    let key = key_spots[rng.gen_range(0, key_spots.len())];
+
+    for region in spawn_regions.iter_mut() {
+        region.tiles.retain(|pos| !in_room(&room, *pos));
+    }
+    spawn_regions.retain(|region| !region.tiles.is_empty());
+    Some(TreasureRoom{ lock: depth, key, loot: SpawnRegion::from_rect(&room) })
+}
+
+// The key, and a few items from deeper down for the room
+pub fn spawn_treasure(world : &mut World, treasure : &TreasureRoom, depth : i32, rng : &mut GameRng) {
+    spawner::key(world, treasure.key.x, treasure.key.y, treasure.lock);
+
+    let mut spots = treasure.loot.tiles.clone();
+    let number_of_items = rng.gen_range(MIN_TREASURE, MAX_TREASURE + 1);
+    for _itemn in 0 .. number_of_items {
+        if spots.is_empty() { break; }
+        let pos = spots.remove(rng.gen_range(0, spots.len()));
+        spawner::random_item(world, pos.x, pos.y, depth + TREASURE_DEPTH_BONUS, rng);
+    }
+}
+
+fn in_room(room : &Rect, pos : Point) -> bool {
+    pos.x >= room.x1 && pos.x < room.x2 && pos.y >= room.y1 && pos.y < room.y2
+}
+
+// Every way into a room: the open tiles in the ring just outside it, corners included
+// since mobs and the player can step diagonally
+fn openings(map : &Map, room : &Rect) -> Vec<usize> {
+    let mut result = Vec::new();
+    for y in room.y1 - 1 ..= room.y2 {
+        for x in room.x1 - 1 ..= room.x2 {
+            let on_ring = x == room.x1 - 1 || x == room.x2 || y == room.y1 - 1 || y == room.y2;
+            if !on_ring || x < 0 || y < 0 || x >= map.width || y >= map.height { continue; }
+            let idx = ((y * map.width) + x) as usize;
+            if map.tiles[idx] != TileType::Wall { result.push(idx); }
+        }
+    }
+    result
+}
@@ -1,7 +1,7 @@
 use crate::rltk;
 use crate ::rltk::Console;
 use rltk::{Point, RGB, Rltk};
-use super::{ State };
+use super::{ State, Camera };
 extern crate serde;
 use serde::{Serialize, Deserialize};
 
@@ -19,8 +19,10 @@ impl Particle {
         Particle{ position, fg, bg, glyph, lifetime_ms }
     }
 
-    pub fn render(&self, ctx : &mut Rltk) {
-        ctx.set(self.position.x, self.position.y, self.fg, self.bg, self.glyph);
+    pub fn render(&self, ctx : &mut Rltk, camera : &Camera) {
+        if let Some(screen_pos) = camera.world_to_screen(self.position) {
+            ctx.set(screen_pos.x, screen_pos.y, self.fg, self.bg, self.glyph);
+        }
     }
 }
 
@@ -0,0 +1,2 @@
+extern crate rltk;
+pub mod game;
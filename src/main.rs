@@ -1,10 +1,42 @@
 extern crate rltk;
 use rltk::Rltk;
-mod game;
+use rusty_roguelike::game;
 
 fn main() {
-    let gs = game::State::new_menu();
-    let mut context = Rltk::init_simple8x8(80, 50, "Rusty Roguelike", "resources");
+    // Optional: --seed <number> to play a specific dungeon, --replay <file> to watch a recorded game,
+    // --mapgen-report <number> to build that many levels per depth and print how the map builders did
+    let args : Vec<String> = std::env::args().collect();
+    let mut seed : Option<u64> = None;
+    let mut replay_file : Option<String> = None;
+    let mut mapgen_levels : Option<u32> = None;
+    for (i, arg) in args.iter().enumerate() {
+        if arg == "--seed" && i + 1 < args.len() {
+            seed = Some(args[i+1].parse().expect("The seed must be a number"));
+        }
+        if arg == "--replay" && i + 1 < args.len() {
+            replay_file = Some(args[i+1].clone());
+        }
+        if arg == "--mapgen-report" && i + 1 < args.len() {
+            mapgen_levels = Some(args[i+1].parse().expect("The number of levels must be a number"));
+        }
+    }
+
+    // Monster and item definitions, and vaults; refuse to start with broken data
+    if let Err(e) = game::load_raws(game::RAW_FILE).and_then(|_| game::load_prefabs(game::PREFAB_DIR)) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    if let Some(levels) = mapgen_levels {
+        print!("{}", game::mapgen_report(levels));
+        return;
+    }
+
+    let gs = match replay_file {
+        None => { game::State::new_menu(seed) }
+        Some(file) => { game::State::new_replay(game::Replay::load(&file).expect("Unable to read replay file")) }
+    };
+    let mut context = Rltk::init_simple8x8(game::SCREEN_WIDTH as u32, game::SCREEN_HEIGHT as u32, "Rusty Roguelike", "resources");
     context.with_post_scanlines(true);
     rltk::main_loop(context, gs);
 }
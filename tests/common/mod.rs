@@ -0,0 +1,40 @@
+// Not every test file uses every helper
+#![allow(dead_code)]
+
+use rusty_roguelike::game::{GameSession, TileType, load_raws, RAW_FILE};
+use rltk::Point;
+
+pub fn new_session(seed : u64) -> GameSession {
+    load_raws(RAW_FILE).unwrap();
+    GameSession::new(seed)
+}
+
+// A session whose player has an empty floor tile to the east, and a speed that
+// makes the cost of each action show in the energy left over afterwards
+pub fn session_facing_floor() -> Option<(GameSession, Point)> {
+    for seed in 0..20 {
+        let mut session = new_session(seed);
+        let start = session.state.player_position();
+        let east = Point::new(start.x + 1, start.y);
+        if tile_at(&session, east) != TileType::Floor { continue; }
+        if session.state.world.positions.iter().any(|(_id, pos)| *pos == east) { continue; }
+        let energy = session.state.world.energy.get_mut(session.state.player_id).unwrap();
+        energy.speed = 70;
+        energy.energy = 100;
+        return Some((session, east));
+    }
+    None
+}
+
+pub fn tile_at(session : &GameSession, pos : Point) -> TileType {
+    session.state.map.tiles[((pos.y * session.state.map.width) + pos.x) as usize]
+}
+
+pub fn set_tile(session : &mut GameSession, pos : Point, tile : TileType) {
+    let width = session.state.map.width;
+    session.state.map.tiles[((pos.y * width) + pos.x) as usize] = tile;
+}
+
+pub fn player_energy(session : &GameSession) -> i32 {
+    session.state.world.energy.get(session.state.player_id).unwrap().energy
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use rusty_roguelike::game::{Command, TileType, Item, ItemType, Name};
+use rltk::Point;
+use common::{session_facing_floor, tile_at, set_tile, player_energy};
+
+#[test]
+fn bumping_a_closed_door_opens_it_and_closing_it_takes_a_turn() {
+    let (mut session, east) = session_facing_floor().unwrap();
+    let start = session.state.player_position();
+    set_tile(&mut session, east, TileType::ClosedDoor);
+
+    // Opening costs a normal action (100): 100 - 100 + 70 + 70
+    session.issue(Command::Move(1, 0));
+    assert!(tile_at(&session, east) == TileType::OpenDoor);
+    assert_eq!(session.state.player_position(), start);
+    assert_eq!(player_energy(&session), 140);
+
+    session.issue(Command::CloseDoor);
+    assert!(tile_at(&session, east) == TileType::ClosedDoor);
+    assert_eq!(player_energy(&session), 110);
+}
+
+#[test]
+fn closing_doors_with_none_open_costs_nothing() {
+    let (mut session, _east) = session_facing_floor().unwrap();
+    let start = session.state.player_position();
+    for y in start.y - 1 ..= start.y + 1 {
+        for x in start.x - 1 ..= start.x + 1 {
+            let pos = Point::new(x, y);
+            if tile_at(&session, pos) == TileType::OpenDoor { set_tile(&mut session, pos, TileType::Floor); }
+        }
+    }
+    session.issue(Command::CloseDoor);
+    assert_eq!(player_energy(&session), 100);
+}
+
+#[test]
+fn a_locked_door_without_its_key_costs_nothing() {
+    let (mut session, east) = session_facing_floor().unwrap();
+    set_tile(&mut session, east, TileType::LockedDoor(99));
+    session.issue(Command::Move(1, 0));
+    assert!(tile_at(&session, east) == TileType::LockedDoor(99));
+    assert_eq!(player_energy(&session), 100);
+}
+
+#[test]
+fn a_key_opens_its_door_and_unlocks_the_others_on_its_lock() {
+    let (mut session, east) = session_facing_floor().unwrap();
+    let other_door = Point::new(1, 1);
+    set_tile(&mut session, east, TileType::LockedDoor(3));
+    set_tile(&mut session, other_door, TileType::LockedDoor(3));
+    let key = session.state.world.create_entity();
+    session.state.world.names.insert(key, Name{ name: "Depth 3 Key".to_string() });
+    session.state.world.items.insert(key, Item{ item_type: ItemType::Key{ lock: 3 } });
+    session.state.player_inventory_mut().items.push(key);
+
+    session.issue(Command::Move(1, 0));
+    assert!(tile_at(&session, east) == TileType::OpenDoor);
+    assert!(tile_at(&session, other_door) == TileType::ClosedDoor);
+    assert!(!session.state.player_inventory().items.contains(&key));
+    assert!(!session.state.world.is_alive(key));
+    assert_eq!(player_energy(&session), 140);
+}
@@ -0,0 +1,93 @@
+use rusty_roguelike::game::{BuiltLevel, BuildLog, GameRng, Map, SpawnRegion, TileType, build_level, validate_level};
+use rand::SeedableRng;
+use rltk::Point;
+
+// A level that is one big room with the stairs in the far corner
+fn room_level() -> BuiltLevel {
+    let mut map = Map::new(40, 30);
+    set_tiles(&mut map, 1, 1, 30, 20, TileType::Floor);
+    set_tiles(&mut map, 30, 20, 30, 20, TileType::Stairs);
+    let room : Vec<Point> = (2..30).flat_map(|x| (2..20).map(move |y| Point::new(x, y))).collect();
+    BuiltLevel{ map, start: Point::new(2, 2), spawn_regions: vec![SpawnRegion{ tiles: room }], fixed_spawns: Vec::new(), treasure: None }
+}
+
+fn set_tiles(map : &mut Map, x1 : i32, y1 : i32, x2 : i32, y2 : i32, tile : TileType) {
+    for y in y1 ..= y2 {
+        for x in x1 ..= x2 {
+            map.tiles[((y * map.width) + x) as usize] = tile;
+        }
+    }
+}
+
+fn tile_at(map : &Map, x : i32, y : i32) -> TileType {
+    map.tiles[((y * map.width) + x) as usize]
+}
+
+#[test]
+fn a_good_level_needs_no_repairs() {
+    let mut level = room_level();
+    assert_eq!(validate_level(&mut level), Ok(false));
+}
+
+#[test]
+fn unreachable_floor_is_walled_up() {
+    let mut level = room_level();
+    set_tiles(&mut level.map, 34, 2, 37, 5, TileType::Floor);
+    level.spawn_regions.push(SpawnRegion{ tiles: vec![Point::new(35, 3)] });
+    assert_eq!(validate_level(&mut level), Ok(true));
+    assert!(tile_at(&level.map, 35, 3) == TileType::Wall);
+    assert_eq!(level.spawn_regions.len(), 1);
+}
+
+#[test]
+fn the_stairs_must_be_there() {
+    let mut level = room_level();
+    set_tiles(&mut level.map, 30, 20, 30, 20, TileType::Floor);
+    assert_eq!(validate_level(&mut level), Err("there are no stairs down".to_string()));
+}
+
+#[test]
+fn the_stairs_must_be_reachable() {
+    let mut level = room_level();
+    set_tiles(&mut level.map, 30, 20, 30, 20, TileType::Floor);
+    set_tiles(&mut level.map, 35, 25, 35, 25, TileType::Stairs);
+    assert_eq!(validate_level(&mut level), Err("the stairs down can't be reached".to_string()));
+}
+
+#[test]
+fn vault_spawns_must_be_reachable() {
+    let mut level = room_level();
+    set_tiles(&mut level.map, 35, 25, 35, 25, TileType::Floor);
+    level.fixed_spawns.push((Point::new(35, 25), "Mut Hound".to_string()));
+    assert_eq!(validate_level(&mut level), Err("the Mut Hound at 35,25 can't be reached".to_string()));
+}
+
+#[test]
+fn locked_doors_count_as_open() {
+    let mut level = room_level();
+    set_tiles(&mut level.map, 30, 20, 30, 20, TileType::Floor);
+    set_tiles(&mut level.map, 31, 10, 31, 10, TileType::LockedDoor(1));
+    set_tiles(&mut level.map, 32, 10, 32, 10, TileType::Stairs);
+    assert_eq!(validate_level(&mut level), Ok(false));
+}
+
+#[test]
+fn a_level_needs_room_to_move() {
+    let mut level = room_level();
+    level.map = Map::new(40, 30);
+    set_tiles(&mut level.map, 1, 1, 5, 5, TileType::Floor);
+    set_tiles(&mut level.map, 5, 5, 5, 5, TileType::Stairs);
+    level.spawn_regions.clear();
+    assert_eq!(validate_level(&mut level), Err("only 25 tiles can be reached".to_string()));
+}
+
+#[test]
+fn levels_too_small_to_play_fall_back_to_plain_rooms() {
+    // No map this size has enough floor to pass, so every attempt fails
+    let mut rng = GameRng::seed_from_u64(1);
+    let mut log = BuildLog::default();
+    let level = build_level(25, 12, 5, &mut rng, &mut log);
+    assert!(level.map.find_tile(TileType::Stairs).is_some());
+    assert!(level.fixed_spawns.is_empty() && level.treasure.is_none());
+    assert_eq!(log.stats.values().map(|stats| stats.fallbacks).sum::<u32>(), 1);
+}
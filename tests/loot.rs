@@ -0,0 +1,48 @@
+mod common;
+
+use rusty_roguelike::game::{Command, GameSession, EntityId, Inventory, Item, ItemType, Name};
+use rltk::Point;
+use common::{new_session, session_facing_floor};
+
+fn key(session : &mut GameSession, lock : i32) -> EntityId {
+    let key = session.state.world.create_entity();
+    session.state.world.names.insert(key, Name{ name: format!("Depth {} Key", lock) });
+    session.state.world.items.insert(key, Item{ item_type: ItemType::Key{ lock } });
+    key
+}
+
+#[test]
+fn loot_seekers_leave_keys_alone() {
+    let mut session = new_session(2);
+    let mobs = session.state.world.mobs.ids();
+    assert!(!mobs.is_empty());
+    for (_id, mob) in session.state.world.mobs.iter_mut() { mob.ai.seeks_loot = true; }
+
+    // A key under the feet of every mob, and a few turns for them to take it
+    let keys : Vec<(EntityId, Point)> = mobs.iter()
+        .map(|mob| (key(&mut session, 1), *session.state.world.positions.get(*mob).unwrap()))
+        .collect();
+    for (key, pos) in keys.iter() { session.state.world.positions.insert(*key, *pos); }
+    for _turn in 0..10 { session.issue(Command::Wait); }
+
+    for (key, pos) in keys.iter() {
+        assert_eq!(session.state.world.positions.get(*key), Some(pos));
+    }
+}
+
+#[test]
+fn a_mob_drops_what_it_carries_when_it_dies() {
+    let (mut session, east) = session_facing_floor().unwrap();
+    let mob = session.state.world.mobs.ids()[0];
+    session.state.world.positions.insert(mob, east);
+    session.state.world.fighters.get_mut(mob).unwrap().hp = 1;
+    session.state.world.fighters.get_mut(mob).unwrap().defense = 0;
+    let key = key(&mut session, 1);
+    let mut inventory = Inventory::new(5);
+    inventory.items.push(key);
+    session.state.world.inventories.insert(mob, inventory);
+
+    session.issue(Command::Move(1, 0));
+    assert!(!session.state.world.is_alive(mob));
+    assert_eq!(session.state.world.positions.get(key), Some(&east));
+}
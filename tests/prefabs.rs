@@ -0,0 +1,72 @@
+use rusty_roguelike::game::{parse_prefab, PREFAB_DIR};
+use std::fs;
+
+const KENNEL : &str = "depth: 0-4
+h: Mut Hound
+
+#####
+#h.h#
+##.##
+";
+
+fn error_for(text : &str) -> String {
+    match parse_prefab("test", text) {
+        Err(e) => e,
+        Ok(prefab) => panic!("expected {} not to parse", prefab.name)
+    }
+}
+
+#[test]
+fn the_shipped_vaults_parse() {
+    for entry in fs::read_dir(PREFAB_DIR).unwrap() {
+        let path = entry.unwrap().path();
+        let text = fs::read_to_string(&path).unwrap();
+        if let Err(e) = parse_prefab(&path.to_string_lossy(), &text) {
+            panic!("{} {}", path.display(), e);
+        }
+    }
+}
+
+#[test]
+fn a_vault_parses() {
+    let prefab = parse_prefab("kennel", KENNEL).unwrap();
+    assert_eq!(prefab.name, "kennel");
+}
+
+#[test]
+fn a_vault_needs_a_depth() {
+    assert_eq!(error_for(&KENNEL.replace("depth: 0-4\n", "")), "needs a depth tag");
+}
+
+#[test]
+fn depths_must_make_sense() {
+    assert_eq!(error_for(&KENNEL.replace("0-4", "4-2")), "has an impossible depth range");
+    assert_eq!(error_for(&KENNEL.replace("0-4", "deep")), "\"deep\" is not a depth");
+    assert!(parse_prefab("test", &KENNEL.replace("0-4", "3+")).is_ok());
+}
+
+#[test]
+fn tags_are_depths_or_single_letters() {
+    assert_eq!(error_for(&KENNEL.replace("h: Mut Hound", "hound: Mut Hound")), "unknown tag \"hound\"");
+    assert_eq!(error_for(&KENNEL.replace("h: Mut Hound", "Mut Hound")), "\"Mut Hound\" should be a tag, like \"depth: 1-5\"");
+}
+
+#[test]
+fn every_map_letter_needs_a_tag() {
+    assert_eq!(error_for(&KENNEL.replace("#h.h#", "#h.g#")), "'g' in the map isn't a tile or a tagged letter");
+}
+
+#[test]
+fn a_vault_needs_a_way_in() {
+    assert_eq!(error_for(&KENNEL.replace("##.##", "#####")), "needs a floor tile on its edge to be entered by");
+}
+
+#[test]
+fn a_vault_has_at_most_one_stairs() {
+    assert_eq!(error_for(&KENNEL.replace("#h.h#", "#>.>#")), "has more than one stairs down");
+}
+
+#[test]
+fn a_vault_needs_a_map() {
+    assert_eq!(error_for("depth: 1\n\n"), "has no map");
+}
@@ -0,0 +1,80 @@
+use rusty_roguelike::game::{load_raws, RawError, RAW_FILE};
+use serde_json::{json, Value};
+use std::fs;
+
+fn shipped_raws() -> Value {
+    serde_json::from_str(&fs::read_to_string(RAW_FILE).unwrap()).unwrap()
+}
+
+// Writes the raws to a file of their own and tries to load them. A failed load
+// leaves the raws that are already loaded alone, so this can't upset other tests.
+fn load_changed(name : &str, raws : &Value) -> Result<(), RawError> {
+    let path = std::env::temp_dir().join(format!("rusty_roguelike_raws_{}.json", name));
+    fs::write(&path, raws.to_string()).unwrap();
+    let result = load_raws(&path.to_string_lossy());
+    let _ = fs::remove_file(&path);
+    result
+}
+
+fn invalid_because(name : &str, raws : &Value) -> String {
+    match load_changed(name, raws) {
+        Err(RawError::Invalid(_path, message)) => message,
+        Err(e) => panic!("expected the raws to be invalid, but got: {}", e),
+        Ok(()) => panic!("expected the raws to be invalid, but they loaded")
+    }
+}
+
+#[test]
+fn the_shipped_raws_load() {
+    load_raws(RAW_FILE).unwrap();
+}
+
+#[test]
+fn broken_json_is_a_parse_error() {
+    let path = std::env::temp_dir().join("rusty_roguelike_raws_broken.json");
+    fs::write(&path, "{ \"mobs\" : [").unwrap();
+    let result = load_raws(&path.to_string_lossy());
+    let _ = fs::remove_file(&path);
+    assert!(matches!(result, Err(RawError::Parse(_, _))));
+}
+
+#[test]
+fn a_missing_file_is_an_io_error() {
+    assert!(matches!(load_raws("./raws/no_such_file.json"), Err(RawError::Io(_, _))));
+}
+
+#[test]
+fn there_must_be_mobs() {
+    let mut raws = shipped_raws();
+    raws["mobs"] = json!([]);
+    assert_eq!(invalid_because("no_mobs", &raws), "no mobs are defined");
+}
+
+#[test]
+fn names_must_be_unique() {
+    let mut raws = shipped_raws();
+    let name = raws["mobs"][0]["name"].clone();
+    raws["items"][0]["name"] = name.clone();
+    assert_eq!(invalid_because("duplicate", &raws), format!("{} is defined more than once", name));
+}
+
+#[test]
+fn mobs_need_hit_points() {
+    let mut raws = shipped_raws();
+    raws["mobs"][0]["fighter"]["max_hp"] = json!(0);
+    assert!(invalid_because("no_hp", &raws).ends_with("needs a max_hp of at least 1"));
+}
+
+#[test]
+fn flee_below_is_a_percentage() {
+    let mut raws = shipped_raws();
+    raws["mobs"][0]["ai"] = json!({ "flee_below" : 150 });
+    assert!(invalid_because("flee_below", &raws).ends_with("needs a flee_below between 0 and 100"));
+}
+
+#[test]
+fn items_must_do_something() {
+    let mut raws = shipped_raws();
+    raws["items"] = json!([{ "name" : "Pebble", "glyph" : 7, "colour" : [128, 128, 128], "spawn" : { "min_depth" : 1, "weight" : 1 } }]);
+    assert_eq!(invalid_because("pebble", &raws), "item \"Pebble\" needs an effect, an equippable or a light section");
+}
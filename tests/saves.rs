@@ -0,0 +1,79 @@
+use rusty_roguelike::game::{GameSession, Command, Name, parse_save, save_game, load_game, list_slots, SlotInfo, SaveError, SAVE_VERSION, load_raws, RAW_FILE};
+
+const SUMMARY : &str = r#"{"name":"Player","depth":1,"level":1,"last_played":0}"#;
+
+// A save as save_game writes it: a header line, then the game
+fn save_text(version : u64) -> (String, String) {
+    load_raws(RAW_FILE).unwrap();
+    let mut session = GameSession::new(5);
+    session.issue(Command::Move(1, 0));
+    let game = serde_json::to_string(&session.state).unwrap();
+    (format!("{{\"version\":{},\"summary\":{}}}\n{}", version, SUMMARY, game), game)
+}
+
+fn incompatible_because(data : &str) -> String {
+    match parse_save(data) {
+        Err(SaveError::Incompatible(e)) => e,
+        Err(e) => panic!("expected an incompatible save, but got: {}", e),
+        Ok(_) => panic!("expected an incompatible save, but it loaded")
+    }
+}
+
+#[test]
+fn a_current_save_loads() {
+    let (data, game) = save_text(SAVE_VERSION);
+    let loaded = parse_save(&data).unwrap();
+    assert_eq!(serde_json::to_string(&loaded).unwrap(), game);
+}
+
+#[test]
+fn saves_from_newer_versions_are_rejected() {
+    let (data, _game) = save_text(SAVE_VERSION + 1);
+    assert_eq!(incompatible_because(&data), format!("it was made by a newer version of the game, save version {}", SAVE_VERSION + 1));
+}
+
+#[test]
+fn saves_without_a_known_version_are_rejected() {
+    let (data, _game) = save_text(0);
+    assert_eq!(incompatible_because(&data), "unknown save version 0");
+    let (_data, game) = save_text(SAVE_VERSION);
+    assert_eq!(incompatible_because(&game), "it was made before save files had a version");
+}
+
+#[test]
+fn damaged_saves_are_rejected() {
+    assert!(matches!(parse_save("{garbage"), Err(SaveError::Damaged(_))));
+    let header = format!("{{\"version\":{},\"summary\":{}}}", SAVE_VERSION, SUMMARY);
+    assert!(matches!(parse_save(&header), Err(SaveError::Damaged(_))));
+    assert!(matches!(parse_save(&format!("{}\n{{\"map\":1}}", header)), Err(SaveError::Damaged(_))));
+}
+
+// The main menu lists the slots from the headers alone, and a slot's save is gone
+// once it has been loaded
+#[cfg(target_os = "linux")]
+#[test]
+fn slots_list_their_headers_and_load() {
+    let home = std::env::temp_dir().join("rusty_roguelike_saves_test");
+    let _ = std::fs::remove_dir_all(&home);
+    std::env::set_var("XDG_DATA_HOME", &home);
+    let (_data, game) = save_text(SAVE_VERSION);
+    let mut session = GameSession::from_state(serde_json::from_str(&game).unwrap());
+    session.state.world.names.insert(session.state.player_id, Name{ name: "Bob".to_string() });
+    save_game(&session.state, 2).unwrap();
+
+    let file = home.join("rusty_roguelike").join("slot3.json");
+    let data = std::fs::read_to_string(&file).unwrap();
+    let (header, _game) = data.split_once('\n').unwrap();
+    assert!(!header.contains("world"));
+    match &list_slots()[2] {
+        SlotInfo::Saved(summary) => { assert_eq!(summary.name, "Bob"); assert_eq!(summary.level, 1); }
+        _ => panic!("expected slot 3 to hold a save")
+    }
+    assert!(matches!(list_slots()[0], SlotInfo::Empty));
+
+    let loaded = load_game(2).unwrap();
+    assert_eq!(loaded.world.name(loaded.player_id), "Bob");
+    assert!(!file.exists());
+    std::fs::write(&file, "{garbage").unwrap();
+    assert!(matches!(list_slots()[2], SlotInfo::Unreadable(_)));
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use rusty_roguelike::game::{Command, TickType};
+use common::{new_session, session_facing_floor};
+
+#[test]
+fn moving_onto_floor_moves_the_player() {
+    let (mut session, east) = session_facing_floor().unwrap();
+    session.issue(Command::Move(1, 0));
+    assert_eq!(session.state.player_position(), east);
+    assert!(session.state.game_state == TickType::PlayersTurn);
+}
+
+#[test]
+fn menus_open_and_close_without_taking_a_turn() {
+    let mut session = new_session(3);
+    let before = serde_json::to_string(&session.state.world).unwrap();
+    session.issue(Command::ShowHelp);
+    assert!(session.state.game_state == TickType::HelpMenu);
+    session.issue(Command::Wait);
+    assert!(session.state.game_state == TickType::HelpMenu);
+    session.issue(Command::Cancel);
+    assert!(session.state.game_state == TickType::PlayersTurn);
+    assert_eq!(before, serde_json::to_string(&session.state.world).unwrap());
+}
+
+#[test]
+fn the_game_is_over_when_the_player_dies() {
+    let mut session = new_session(3);
+    session.state.player_fighter_mut().hp = 0;
+    session.state.player_fighter_mut().dead = true;
+    session.issue(Command::Wait);
+    assert!(session.is_game_over());
+}
+
+#[test]
+fn moving_off_the_map_is_bumping_a_wall() {
+    let mut session = new_session(3);
+    let start = session.state.player_position();
+    for (delta_x, delta_y) in [(-1000, 0), (1000, 0), (0, -1000), (0, 1000), (-start.x, -start.y)].iter() {
+        session.issue(Command::Move(*delta_x, *delta_y));
+        assert_eq!(session.state.player_position(), start);
+    }
+}
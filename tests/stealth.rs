@@ -0,0 +1,33 @@
+mod common;
+
+use rusty_roguelike::game::{Command, TileType};
+use common::{new_session, session_facing_floor, tile_at, set_tile, player_energy};
+
+#[test]
+fn sneaking_toggles_without_taking_a_turn() {
+    let mut session = new_session(3);
+    let before = serde_json::to_string(&session.state.world).unwrap();
+    session.issue(Command::ToggleSneak);
+    assert!(session.state.player().sneaking);
+    session.issue(Command::ToggleSneak);
+    assert!(!session.state.player().sneaking);
+    assert_eq!(before, serde_json::to_string(&session.state.world).unwrap());
+}
+
+#[test]
+fn sneaking_only_slows_down_steps() {
+    let (mut session, east) = session_facing_floor().unwrap();
+    set_tile(&mut session, east, TileType::ClosedDoor);
+    session.issue(Command::ToggleSneak);
+
+    // Opening the door costs a normal action (100): 100 - 100 + 70 + 70
+    session.issue(Command::Move(1, 0));
+    assert!(tile_at(&session, east) == TileType::OpenDoor);
+    assert_eq!(player_energy(&session), 140);
+
+    // Stepping through it costs double (200): 100 - 200 + 70 + 70 + 70
+    session.state.world.energy.get_mut(session.state.player_id).unwrap().energy = 100;
+    session.issue(Command::Move(1, 0));
+    assert_eq!(session.state.player_position(), east);
+    assert_eq!(player_energy(&session), 110);
+}